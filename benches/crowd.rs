@@ -0,0 +1,34 @@
+//! Times simulation ticks with a crowd of 5,000 enemies around the player, and
+//! fails if the average tick doesn't fit in a 60 Hz frame. Run it with
+//! `cargo bench --bench crowd`.
+
+use horde::HeadlessGame;
+use std::time::{Duration, Instant};
+
+const ENEMIES: usize = 5000;
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+/// Ticks to let the crowd close in on the player before timing.
+const WARMUP_TICKS: u64 = 900;
+const TIMED_TICKS: u32 = 300;
+
+fn main() {
+    let mut game = HeadlessGame::crowd(ENEMIES);
+    game.step(WARMUP_TICKS);
+    let enemies = game.summary().enemies;
+    let start = Instant::now();
+    game.step(TIMED_TICKS.into());
+    let per_tick = start.elapsed() / TIMED_TICKS;
+
+    println!(
+        "{} enemies: {:.2} ms per tick (budget {:.2} ms)",
+        enemies,
+        per_tick.as_secs_f64() * 1000.0,
+        FRAME_BUDGET.as_secs_f64() * 1000.0
+    );
+    assert!(
+        enemies >= ENEMIES * 9 / 10,
+        "Only {} enemies spawned",
+        enemies
+    );
+    assert!(per_tick <= FRAME_BUDGET, "Ticks are over the frame budget");
+}
@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use crate::{CameraView, Collider, Velocity};
+
+/// The region of the world that bouncing projectiles are kept inside.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ArenaBounds {
+    /// Whatever the camera shows at the time. See `CameraView`.
+    #[default]
+    CameraView,
+    /// A fixed rectangle of the world.
+    Fixed { min: Vec2, max: Vec2 },
+}
+
+impl ArenaBounds {
+    /// The bottom left and top right corners of the arena.
+    pub fn rect(&self, view: &CameraView) -> (Vec2, Vec2) {
+        match *self {
+            ArenaBounds::CameraView => (
+                view.center - view.half_extents,
+                view.center + view.half_extents,
+            ),
+            ArenaBounds::Fixed { min, max } => (min, max),
+        }
+    }
+}
+
+/// Bounces off the edges of the `ArenaBounds` rather than leaving them.
+#[derive(Component)]
+pub(crate) struct BounceOffArenaEdges;
+
+/// Reflects anything that crossed an edge of the arena this tick back inside,
+/// by as far as it went past the edge, and turns it to face away from the edge
+/// so that it can't get stuck bouncing back and forth outside.
+pub(crate) fn bounce_off_arena_edges(
+    bounds: Res<ArenaBounds>,
+    view: Res<CameraView>,
+    mut bouncers: Query<(&mut Transform, &mut Velocity, &Collider), With<BounceOffArenaEdges>>,
+) {
+    let (min, max) = bounds.rect(&view);
+    for (mut transform, mut velocity, collider) in bouncers.iter_mut() {
+        let center = collider.center(transform.translation);
+        let low = min + collider.half_extents();
+        let high = max - collider.half_extents();
+        let mut bounced = center;
+        for axis in 0..2 {
+            if low[axis] > high[axis] {
+                // Too big to fit inside, so there's nowhere to bounce to.
+                continue;
+            }
+            if center[axis] < low[axis] {
+                bounced[axis] = (2.0 * low[axis] - center[axis]).min(high[axis]);
+                velocity.direction[axis] = velocity.direction[axis].abs();
+            } else if center[axis] > high[axis] {
+                bounced[axis] = (2.0 * high[axis] - center[axis]).max(low[axis]);
+                velocity.direction[axis] = -velocity.direction[axis].abs();
+            }
+        }
+        if bounced != center {
+            transform.translation += (bounced - center).extend(0.0);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{DeathEvent, Enemy, InvincibilityWindow, Player, SimulationClock, Velocity};
+
+/// The part of the world the game camera shows. It follows the player as part
+/// of the simulation, so that gameplay can rely on it even without a window;
+/// the camera itself only draws what it says.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraView {
+    pub center: Vec2,
+    pub half_extents: Vec2,
+    /// Where the center was at the start of the latest tick.
+    previous_center: Vec2,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        CameraView {
+            center: Vec2::ZERO,
+            half_extents: Vec2::new(320.0, 200.0),
+            previous_center: Vec2::ZERO,
+        }
+    }
+}
+
+impl CameraView {
+    /// Moves straight to `center`, without easing over from where it was.
+    pub fn jump_to(&mut self, center: Vec2) {
+        self.center = center;
+        self.previous_center = center;
+    }
+
+    /// Where the camera is drawn from, part way between the last two ticks.
+    pub fn interpolated_center(&self, overstep: f32) -> Vec2 {
+        self.previous_center.lerp(self.center, overstep)
+    }
+}
+
+/// How the camera follows the player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraFollow {
+    /// How quickly the camera catches up, as the rate at which the remaining
+    /// distance shrinks per second. Higher is snappier.
+    pub smoothing: f32,
+    /// Half the size of a box around the camera's center that the player can
+    /// move around in without the camera following.
+    pub dead_zone: Option<Vec2>,
+    /// How far ahead of the player, in the direction they're moving, the
+    /// camera aims.
+    pub look_ahead: f32,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        CameraFollow {
+            smoothing: 5.0,
+            dead_zone: None,
+            look_ahead: 40.0,
+        }
+    }
+}
+
+pub(crate) fn follow_player(
+    clock: Res<SimulationClock>,
+    follow: Res<CameraFollow>,
+    mut view: ResMut<CameraView>,
+    players: Query<(&Transform, &Velocity), With<Player>>,
+) {
+    view.previous_center = view.center;
+    let (transform, velocity) = match players.iter().next() {
+        Some(player) => player,
+        None => return,
+    };
+    let target = (transform.translation + velocity.direction * follow.look_ahead).truncate();
+    let mut offset = target - view.center;
+    if let Some(dead_zone) = follow.dead_zone {
+        offset -= offset.clamp(-dead_zone, dead_zone);
+    }
+    let catch_up = 1.0 - (-follow.smoothing * clock.delta_seconds()).exp();
+    view.center += offset * catch_up;
+}
+
+/// Despawns enemies that have fallen this far behind the edge of the view.
+/// The wave director will send more.
+const DESPAWN_MARGIN: f32 = 400.0;
+
+pub(crate) fn despawn_distant_enemies(
+    mut commands: Commands,
+    view: Res<CameraView>,
+    mut death_events: EventReader<DeathEvent>,
+    enemies: Query<(Entity, &Transform), With<Enemy>>,
+    mut invincibility_windows: Query<&mut InvincibilityWindow>,
+) {
+    let dead: Vec<Entity> = death_events.iter().map(|event| event.entity).collect();
+    let max_distance = view.half_extents.length() + DESPAWN_MARGIN;
+    for (entity, transform) in enemies.iter() {
+        let distance = transform.translation.truncate().distance(view.center);
+        // Enemies that died this tick are already being despawned.
+        if distance > max_distance && !dead.contains(&entity) {
+            commands.entity(entity).despawn_recursive();
+            // This isn't a death, so nothing else forgets the enemy's hits.
+            for mut window in invincibility_windows.iter_mut() {
+                window.damage_sources.remove(&entity);
+            }
+        }
+    }
+}
+
+/// The camera the world is drawn with, as opposed to the UI camera.
+#[derive(Component)]
+struct MainCamera;
+
+fn setup_cameras(mut commands: Commands) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+fn resize_camera_view(
+    windows: Res<Windows>,
+    mut view: ResMut<CameraView>,
+    cameras: Query<&Transform, With<MainCamera>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    for transform in cameras.iter() {
+        view.half_extents =
+            Vec2::new(window.width(), window.height()) / 2.0 * transform.scale.truncate();
+    }
+}
+
+fn move_camera(
+    clock: Res<SimulationClock>,
+    view: Res<CameraView>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    let center = view.interpolated_center(clock.overstep());
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
+
+const TILE_SIZE: f32 = 64.0;
+const TILE_COLORS: [Color; 2] = [Color::rgb(0.06, 0.06, 0.06), Color::rgb(0.08, 0.08, 0.08)];
+
+/// One square of the checkered floor. The floor only covers the view, and is
+/// laid out again around the camera every frame, so the world has no edge.
+#[derive(Component)]
+struct BackgroundTile;
+
+fn update_background(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    view: Res<CameraView>,
+    mut tiles: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<BackgroundTile>>,
+) {
+    let center = view.interpolated_center(clock.overstep());
+    let min = ((center - view.half_extents) / TILE_SIZE)
+        .floor()
+        .as_ivec2();
+    let max = ((center + view.half_extents) / TILE_SIZE)
+        .floor()
+        .as_ivec2();
+    let mut cells = (min.y..=max.y).flat_map(|y| (min.x..=max.x).map(move |x| IVec2::new(x, y)));
+    // Just behind everything else, but still in front of the camera's far plane.
+    let place = |cell: IVec2| Transform {
+        translation: ((cell.as_vec2() + 0.5) * TILE_SIZE).extend(-0.05),
+        scale: Vec3::new(TILE_SIZE, TILE_SIZE, 1.0),
+        ..default()
+    };
+    let color = |cell: IVec2| TILE_COLORS[((cell.x + cell.y) & 1) as usize];
+    for (mut transform, mut sprite, mut visibility) in tiles.iter_mut() {
+        match cells.next() {
+            Some(cell) => {
+                *transform = place(cell);
+                sprite.color = color(cell);
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+    for cell in cells {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: color(cell),
+                    ..default()
+                },
+                transform: place(cell),
+                ..default()
+            })
+            .insert(BackgroundTile);
+    }
+}
+
+/// The game camera following `CameraView`, and the floor beneath it.
+pub(crate) fn add_camera(app: &mut App) {
+    app.add_startup_system(setup_cameras)
+        .add_system_to_stage(CoreStage::PreUpdate, resize_camera_view)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            move_camera.before(TransformSystem::TransformPropagate),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_background.before(TransformSystem::TransformPropagate),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{ecs::event::Events, utils::HashMap};
+
+    #[test]
+    fn despawned_enemies_are_forgotten() {
+        let mut world = World::new();
+        world.insert_resource(CameraView::default());
+        world.init_resource::<Events<DeathEvent>>();
+        let near = world
+            .spawn()
+            .insert(Transform::from_xyz(100.0, 0.0, 0.0))
+            .insert(Enemy)
+            .id();
+        let far = world
+            .spawn()
+            .insert(Transform::from_xyz(5000.0, 0.0, 0.0))
+            .insert(Enemy)
+            .id();
+        let mut damage_sources = HashMap::default();
+        for enemy in [near, far] {
+            damage_sources.insert(enemy, Timer::from_seconds(0.5, false));
+        }
+        let player = world
+            .spawn()
+            .insert(InvincibilityWindow { damage_sources })
+            .id();
+
+        SystemStage::single_threaded()
+            .with_system(despawn_distant_enemies)
+            .run(&mut world);
+
+        assert!(world.get_entity(far).is_none());
+        assert!(world.get_entity(near).is_some());
+        let window = world.get::<InvincibilityWindow>(player).unwrap();
+        assert!(window.damage_sources.contains_key(&near));
+        assert!(!window.damage_sources.contains_key(&far));
+    }
+}
@@ -0,0 +1,560 @@
+use bevy::prelude::*;
+
+/// Which collision layers an entity is on, and which layers it wants to hear
+/// about. A `CollisionEvent` with this entity as the collider is only sent for
+/// obstacles on one of its filter layers. Entities without this component
+/// never collide.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionLayers {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl CollisionLayers {
+    pub const NONE: u32 = 0;
+    pub const PLAYER: u32 = 1 << 0;
+    pub const ENEMY: u32 = 1 << 1;
+    pub const PLAYER_PROJECTILE: u32 = 1 << 2;
+    pub const PICKUP: u32 = 1 << 3;
+
+    pub fn new(memberships: u32, filters: u32) -> Self {
+        CollisionLayers {
+            memberships,
+            filters,
+        }
+    }
+
+    /// Whether this entity hears about touching `other`. This only looks at
+    /// this entity's filters, so it can be true one way round and not the
+    /// other.
+    pub fn interacts_with(&self, other: &CollisionLayers) -> bool {
+        self.filters & other.memberships != 0
+    }
+}
+
+/// Makes a collider test the whole path it moved along during a tick, rather
+/// than only where it ended up, so that fast projectiles can't skip past
+/// whatever is in the way.
+#[derive(Component, Debug, Default)]
+pub struct Sweep {
+    pub(crate) start: Vec3,
+    /// What the collider was already touching at the end of the last tick.
+    /// Staying in contact with these doesn't count as a new hit.
+    pub(crate) touching: Vec<Entity>,
+}
+
+pub(crate) fn start_sweeps(mut query: Query<(&Transform, &mut Sweep)>) {
+    for (transform, mut sweep) in query.iter_mut() {
+        sweep.start = transform.translation;
+    }
+}
+
+/// How hard a solid body is to push out of the way when it overlaps another.
+/// Bodies without one have a mass of 1. An infinite mass is never pushed.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Mass(pub f32);
+
+/// Which ways a solid body was pushed out of others during the last tick.
+/// Moving back into them is cancelled, so that a crowd chasing into a body
+/// doesn't squeeze it into whatever is in front of it.
+#[derive(Component, Debug, Default)]
+pub struct Blocked {
+    pub(crate) normals: Vec<Vec2>,
+}
+
+impl Blocked {
+    /// `movement` without the parts that point into anything it's pressed
+    /// against.
+    pub fn slide(&self, mut movement: Vec2) -> Vec2 {
+        for &normal in &self.normals {
+            let into = movement.dot(normal);
+            if into < 0.0 {
+                movement -= normal * into;
+            }
+        }
+        movement
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Aabb { half_extents: Vec2 },
+    Circle { radius: f32 },
+}
+
+/// The hitbox of an entity, positioned relative to its `Transform`'s
+/// translation. The transform's scale and the sprite's size play no part in
+/// collisions.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    pub offset: Vec2,
+}
+
+/// How two colliders overlap. `normal` points from the other collider towards
+/// this one, and is zero when their centers coincide.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub normal: Vec2,
+    pub depth: f32,
+}
+
+impl Collider {
+    pub fn aabb(size: Vec2) -> Self {
+        Collider {
+            shape: ColliderShape::Aabb {
+                half_extents: size / 2.0,
+            },
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Collider {
+            shape: ColliderShape::Circle { radius },
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn center(&self, translation: Vec3) -> Vec2 {
+        translation.truncate() + self.offset
+    }
+
+    /// Half the size of the smallest axis-aligned box around the shape.
+    pub fn half_extents(&self) -> Vec2 {
+        match self.shape {
+            ColliderShape::Aabb { half_extents } => half_extents,
+            ColliderShape::Circle { radius } => Vec2::splat(radius),
+        }
+    }
+
+    pub fn overlaps(&self, translation: Vec3, other: &Collider, other_translation: Vec3) -> bool {
+        self.contact(translation, other, other_translation)
+            .is_some()
+    }
+
+    /// How far along the move from `start` to `end`, from 0 to 1, this
+    /// collider first touches `other`, if it does at all.
+    pub fn sweep(
+        &self,
+        start: Vec3,
+        end: Vec3,
+        other: &Collider,
+        other_translation: Vec3,
+    ) -> Option<f32> {
+        if self.overlaps(start, other, other_translation) {
+            return Some(0.0);
+        }
+        // Sweeping one shape against another is the same as casting a ray
+        // from its center against their Minkowski sum, which for boxes and
+        // circles is always a box with rounded corners.
+        let (half_extents, radius) = match (self.shape, other.shape) {
+            (
+                ColliderShape::Aabb { half_extents },
+                ColliderShape::Aabb {
+                    half_extents: other_half_extents,
+                },
+            ) => (half_extents + other_half_extents, 0.0),
+            (
+                ColliderShape::Circle { radius },
+                ColliderShape::Circle {
+                    radius: other_radius,
+                },
+            ) => (Vec2::ZERO, radius + other_radius),
+            (ColliderShape::Aabb { half_extents }, ColliderShape::Circle { radius })
+            | (ColliderShape::Circle { radius }, ColliderShape::Aabb { half_extents }) => {
+                (half_extents, radius)
+            }
+        };
+        let origin = self.center(start) - other.center(other_translation);
+        let direction = (end - start).truncate();
+        ray_rounded_box(origin, direction, half_extents, radius)
+    }
+
+    pub fn contact(
+        &self,
+        translation: Vec3,
+        other: &Collider,
+        other_translation: Vec3,
+    ) -> Option<Contact> {
+        let center = self.center(translation);
+        let other_center = other.center(other_translation);
+        match (self.shape, other.shape) {
+            (
+                ColliderShape::Aabb { half_extents },
+                ColliderShape::Aabb {
+                    half_extents: other_half_extents,
+                },
+            ) => aabb_aabb(center, half_extents, other_center, other_half_extents),
+            (
+                ColliderShape::Circle { radius },
+                ColliderShape::Circle {
+                    radius: other_radius,
+                },
+            ) => circle_circle(center, radius, other_center, other_radius),
+            (ColliderShape::Circle { radius }, ColliderShape::Aabb { half_extents }) => {
+                circle_aabb(center, radius, other_center, half_extents)
+            }
+            (ColliderShape::Aabb { half_extents }, ColliderShape::Circle { radius }) => {
+                circle_aabb(other_center, radius, center, half_extents).map(|contact| Contact {
+                    normal: -contact.normal,
+                    depth: contact.depth,
+                })
+            }
+        }
+    }
+}
+
+fn aabb_aabb(a: Vec2, a_half: Vec2, b: Vec2, b_half: Vec2) -> Option<Contact> {
+    let delta = a - b;
+    let overlap = a_half + b_half - delta.abs();
+    if overlap.x <= 0.0 || overlap.y <= 0.0 {
+        return None;
+    }
+    // Push out along whichever axis needs the smaller move.
+    let normal = if overlap.x < overlap.y {
+        Vec2::new(sign_or_zero(delta.x), 0.0)
+    } else {
+        Vec2::new(0.0, sign_or_zero(delta.y))
+    };
+    Some(Contact {
+        normal,
+        depth: overlap.x.min(overlap.y),
+    })
+}
+
+fn circle_circle(a: Vec2, a_radius: f32, b: Vec2, b_radius: f32) -> Option<Contact> {
+    let delta = a - b;
+    let distance = delta.length();
+    let depth = a_radius + b_radius - distance;
+    if depth <= 0.0 {
+        return None;
+    }
+    Some(Contact {
+        normal: delta.normalize_or_zero(),
+        depth,
+    })
+}
+
+fn circle_aabb(circle: Vec2, radius: f32, aabb: Vec2, half_extents: Vec2) -> Option<Contact> {
+    let local = circle - aabb;
+    let closest = local.clamp(-half_extents, half_extents);
+    if closest == local {
+        // The circle's center is inside the box, so treat it as a box.
+        return aabb_aabb(circle, Vec2::splat(radius), aabb, half_extents);
+    }
+    let delta = local - closest;
+    let distance = delta.length();
+    if distance >= radius {
+        return None;
+    }
+    Some(Contact {
+        normal: delta / distance,
+        depth: radius - distance,
+    })
+}
+
+/// The first time in 0..=1 that `origin + t * direction` is inside a box
+/// centered on the origin whose corners are rounded off by `radius`.
+fn ray_rounded_box(origin: Vec2, direction: Vec2, half_extents: Vec2, radius: f32) -> Option<f32> {
+    let mut first: Option<f32> = None;
+    let mut consider = |t: Option<f32>| {
+        if let Some(t) = t {
+            first = Some(first.map_or(t, |first| first.min(t)));
+        }
+    };
+    consider(ray_box(
+        origin,
+        direction,
+        half_extents + Vec2::new(radius, 0.0),
+    ));
+    consider(ray_box(
+        origin,
+        direction,
+        half_extents + Vec2::new(0.0, radius),
+    ));
+    if radius > 0.0 {
+        for corner in [
+            Vec2::new(half_extents.x, half_extents.y),
+            Vec2::new(-half_extents.x, half_extents.y),
+            Vec2::new(half_extents.x, -half_extents.y),
+            Vec2::new(-half_extents.x, -half_extents.y),
+        ] {
+            consider(ray_circle(origin - corner, direction, radius));
+        }
+    }
+    first
+}
+
+fn ray_box(origin: Vec2, direction: Vec2, half_extents: Vec2) -> Option<f32> {
+    if half_extents.x <= 0.0 || half_extents.y <= 0.0 {
+        return None;
+    }
+    let mut t_min = 0.0_f32;
+    let mut t_max = 1.0_f32;
+    for axis in 0..2 {
+        if direction[axis] == 0.0 {
+            if origin[axis].abs() >= half_extents[axis] {
+                return None;
+            }
+        } else {
+            let t1 = (-half_extents[axis] - origin[axis]) / direction[axis];
+            let t2 = (half_extents[axis] - origin[axis]) / direction[axis];
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+            if t_min >= t_max {
+                return None;
+            }
+        }
+    }
+    Some(t_min)
+}
+
+fn ray_circle(origin: Vec2, direction: Vec2, radius: f32) -> Option<f32> {
+    let a = direction.length_squared();
+    let c = origin.length_squared() - radius * radius;
+    if c < 0.0 {
+        return Some(0.0);
+    }
+    if a == 0.0 {
+        return None;
+    }
+    let b = origin.dot(direction);
+    let discriminant = b * b - a * c;
+    if discriminant <= 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / a;
+    if (0.0..=1.0).contains(&t) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+fn sign_or_zero(value: f32) -> f32 {
+    if value == 0.0 {
+        0.0
+    } else {
+        value.signum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_interact_one_way() {
+        let projectile =
+            CollisionLayers::new(CollisionLayers::PLAYER_PROJECTILE, CollisionLayers::ENEMY);
+        let enemy = CollisionLayers::new(CollisionLayers::ENEMY, CollisionLayers::PLAYER);
+        let player = CollisionLayers::new(CollisionLayers::PLAYER, CollisionLayers::PICKUP);
+        let pickup = CollisionLayers::new(CollisionLayers::PICKUP, CollisionLayers::NONE);
+
+        assert!(projectile.interacts_with(&enemy));
+        assert!(!enemy.interacts_with(&projectile));
+        assert!(enemy.interacts_with(&player));
+        assert!(!player.interacts_with(&enemy));
+        assert!(player.interacts_with(&pickup));
+        assert!(!pickup.interacts_with(&player));
+        assert!(!projectile.interacts_with(&projectile));
+    }
+
+    #[test]
+    fn any_shared_layer_is_enough() {
+        let both = CollisionLayers::new(
+            CollisionLayers::ENEMY | CollisionLayers::PICKUP,
+            CollisionLayers::NONE,
+        );
+        let player = CollisionLayers::new(CollisionLayers::PLAYER, CollisionLayers::PICKUP);
+        assert!(player.interacts_with(&both));
+        assert!(!both.interacts_with(&player));
+    }
+
+    fn assert_contact(contact: Option<Contact>, expected: Option<(Vec2, f32)>) {
+        match (contact, expected) {
+            (None, None) => {}
+            (Some(contact), Some((normal, depth))) => assert!(
+                contact.normal.abs_diff_eq(normal, 1e-4) && (contact.depth - depth).abs() < 1e-4,
+                "expected {:?} deep along {:?}, got {:?}",
+                depth,
+                normal,
+                contact
+            ),
+            (contact, expected) => panic!("expected {:?}, got {:?}", expected, contact),
+        }
+    }
+
+    fn assert_time(t: Option<f32>, expected: Option<f32>) {
+        match (t, expected) {
+            (None, None) => {}
+            (Some(t), Some(expected)) if (t - expected).abs() < 1e-4 => {}
+            (t, expected) => panic!("expected {:?}, got {:?}", expected, t),
+        }
+    }
+
+    #[test]
+    fn circle_against_box() {
+        let half = Vec2::splat(10.0);
+        let diagonal = Vec2::ONE.normalize();
+        let cases = [
+            // Touching an edge.
+            (Vec2::new(11.0, 0.0), Some((Vec2::X, 1.0))),
+            (Vec2::new(0.0, -11.5), Some((-Vec2::Y, 0.5))),
+            // Near a corner, where only the corner itself counts.
+            (
+                Vec2::new(11.0, 11.0),
+                Some((diagonal, 2.0 - 2.0_f32.sqrt())),
+            ),
+            (Vec2::new(11.5, 11.5), None),
+            // Just out of reach, and exactly touching.
+            (Vec2::new(12.5, 0.0), None),
+            (Vec2::new(12.0, 0.0), None),
+            // Centers inside the box push out the nearest way.
+            (Vec2::new(8.0, 0.0), Some((Vec2::X, 4.0))),
+            (Vec2::new(0.0, -9.0), Some((-Vec2::Y, 3.0))),
+            (Vec2::ZERO, Some((Vec2::ZERO, 12.0))),
+        ];
+        for (circle, expected) in cases {
+            assert_contact(circle_aabb(circle, 2.0, Vec2::ZERO, half), expected);
+        }
+    }
+
+    #[test]
+    fn box_against_box() {
+        let half = Vec2::splat(5.0);
+        let cases = [
+            (Vec2::new(8.0, 1.0), Some((Vec2::X, 2.0))),
+            (Vec2::new(-1.0, -9.0), Some((-Vec2::Y, 1.0))),
+            (Vec2::new(10.0, 0.0), None),
+            (Vec2::new(9.0, 10.0), None),
+            (Vec2::ZERO, Some((Vec2::ZERO, 10.0))),
+        ];
+        for (center, expected) in cases {
+            assert_contact(aabb_aabb(center, half, Vec2::ZERO, half), expected);
+        }
+    }
+
+    #[test]
+    fn circle_against_circle() {
+        let cases = [
+            (Vec2::new(0.0, 4.0), Some((Vec2::Y, 1.0))),
+            (Vec2::new(-3.0, 4.0), None),
+            (Vec2::new(6.0, 0.0), None),
+            (Vec2::ZERO, Some((Vec2::ZERO, 5.0))),
+        ];
+        for (center, expected) in cases {
+            assert_contact(circle_circle(center, 2.0, Vec2::ZERO, 3.0), expected);
+        }
+    }
+
+    #[test]
+    fn rays_against_boxes() {
+        let half = Vec2::splat(10.0);
+        let cases = [
+            (Vec2::new(-20.0, 0.0), Vec2::new(40.0, 0.0), Some(0.25)),
+            (Vec2::new(0.0, 30.0), Vec2::new(0.0, -40.0), Some(0.5)),
+            // Stopping short, or going the other way.
+            (Vec2::new(-20.0, 0.0), Vec2::new(5.0, 0.0), None),
+            (Vec2::new(-20.0, 0.0), Vec2::new(-40.0, 0.0), None),
+            // Sliding along an edge doesn't count.
+            (Vec2::new(-20.0, 10.0), Vec2::new(40.0, 0.0), None),
+            // Starting inside.
+            (Vec2::new(5.0, 5.0), Vec2::new(40.0, 0.0), Some(0.0)),
+            // Not moving at all.
+            (Vec2::new(5.0, 5.0), Vec2::ZERO, Some(0.0)),
+            (Vec2::new(-20.0, 0.0), Vec2::ZERO, None),
+        ];
+        for (origin, direction, expected) in cases {
+            assert_time(ray_box(origin, direction, half), expected);
+        }
+        assert_time(ray_box(Vec2::ZERO, Vec2::X, Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn rays_against_circles() {
+        let cases = [
+            (Vec2::new(-10.0, 0.0), Vec2::new(20.0, 0.0), Some(0.4)),
+            // Grazing the edge.
+            (Vec2::new(-10.0, 2.0), Vec2::new(20.0, 0.0), None),
+            (Vec2::new(-10.0, 0.0), Vec2::new(5.0, 0.0), None),
+            (Vec2::new(10.0, 0.0), Vec2::new(20.0, 0.0), None),
+            // Starting inside.
+            (Vec2::new(1.0, 0.0), Vec2::new(20.0, 0.0), Some(0.0)),
+            // Not moving at all.
+            (Vec2::new(1.0, 1.0), Vec2::ZERO, Some(0.0)),
+            (Vec2::new(-10.0, 0.0), Vec2::ZERO, None),
+        ];
+        for (origin, direction, expected) in cases {
+            assert_time(ray_circle(origin, direction, 2.0), expected);
+        }
+    }
+
+    #[test]
+    fn rays_against_rounded_boxes() {
+        let half = Vec2::splat(10.0);
+        let cases = [
+            // Straight at a side.
+            (Vec2::new(-22.0, 0.0), Vec2::new(40.0, 0.0), Some(0.25)),
+            // Level with the corner's curve, which it meets before the
+            // straight edge above it.
+            (
+                Vec2::new(-20.0, 11.5),
+                Vec2::new(40.0, 0.0),
+                Some((20.0 - 10.0 - 1.75_f32.sqrt()) / 40.0),
+            ),
+            // Through where a square corner would be, but outside the
+            // rounded one.
+            (Vec2::new(-21.6, 1.6), Vec2::new(20.0, 20.0), None),
+            // Clipping the rounded corner, whose center passes √3.38 to the
+            // side of the path, √200 along it.
+            (
+                Vec2::new(-21.3, 1.3),
+                Vec2::new(20.0, 20.0),
+                Some((200.0_f32.sqrt() - (4.0 - 3.38_f32).sqrt()) / 800.0_f32.sqrt()),
+            ),
+            (Vec2::new(-20.0, 13.0), Vec2::new(40.0, 0.0), None),
+            // Starting inside.
+            (Vec2::new(11.0, 0.0), Vec2::new(40.0, 0.0), Some(0.0)),
+            // Not moving at all.
+            (Vec2::new(11.0, 11.0), Vec2::ZERO, Some(0.0)),
+            (Vec2::new(11.5, 11.5), Vec2::ZERO, None),
+        ];
+        for (origin, direction, expected) in cases {
+            assert_time(ray_rounded_box(origin, direction, half, 2.0), expected);
+        }
+    }
+
+    #[test]
+    fn sweeps_between_shapes() {
+        let start = Vec3::new(-30.0, 0.0, 0.0);
+        let end = Vec3::new(30.0, 0.0, 0.0);
+        let small_box = Collider::aabb(Vec2::splat(4.0));
+        let small_circle = Collider::circle(2.0);
+        let big_box = Collider::aabb(Vec2::splat(20.0));
+        let big_circle = Collider::circle(10.0);
+        let cases = [
+            (small_box, big_box, Some(18.0 / 60.0)),
+            (small_circle, big_box, Some(18.0 / 60.0)),
+            (small_box, big_circle, Some(18.0 / 60.0)),
+            (small_circle, big_circle, Some(18.0 / 60.0)),
+        ];
+        for (collider, other, expected) in cases {
+            assert_time(collider.sweep(start, end, &other, Vec3::ZERO), expected);
+        }
+        // Offsets move the shape, not the path.
+        let offset = big_box.with_offset(Vec2::new(0.0, 13.0));
+        assert_time(small_circle.sweep(start, end, &offset, Vec3::ZERO), None);
+        // Already touching at the start of the move.
+        assert_time(
+            small_circle.sweep(Vec3::new(-11.0, 0.0, 0.0), end, &big_box, Vec3::ZERO),
+            Some(0.0),
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{DeathEvent, Health};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Ice,
+    Lightning,
+    Holy,
+}
+
+/// How much of each type of damage an entity shrugs off, as a fraction. 0.5
+/// halves it, 1 ignores it entirely, and a negative value is a weakness: -0.5
+/// takes half as much again. Types not listed do full damage.
+#[derive(Component, Debug, Default, Clone)]
+pub struct Resistances(pub HashMap<DamageType, f32>);
+
+impl Resistances {
+    pub fn multiplier(&self, kind: DamageType) -> f32 {
+        1.0 - self.0.get(&kind).copied().unwrap_or(0.0).min(1.0)
+    }
+}
+
+/// Asks for `amount` of damage to be dealt to `target`, before the target's
+/// resistances. Hits and damage over time send these rather than touching
+/// `Health` themselves, and `apply_damage` settles them all in one place.
+pub struct DamageRequest {
+    /// What dealt the damage, such as a projectile or an enemy. `None` for
+    /// damage over time.
+    pub source: Option<Entity>,
+    pub target: Entity,
+    /// Who the source belongs to, such as the player that fired a projectile.
+    pub owner: Option<Entity>,
+    /// The id of the weapon behind the damage, whether it hit directly or
+    /// through an effect it applied.
+    pub weapon: Option<String>,
+    pub amount: f32,
+    pub kind: DamageType,
+    pub crit: bool,
+    /// Whether the damage can take the target's last point of health.
+    pub lethal: bool,
+}
+
+/// Sent for every request that `apply_damage` settles, with the health the
+/// target actually lost after resistances. Anything reacting to damage should
+/// listen for these.
+pub struct DamageEvent {
+    pub source: Option<Entity>,
+    pub target: Entity,
+    pub owner: Option<Entity>,
+    pub weapon: Option<String>,
+    pub amount: u32,
+    pub kind: DamageType,
+    pub crit: bool,
+    /// Whether this was the hit that took the target's last point of health.
+    pub fatal: bool,
+}
+
+/// Turns rolled damage into what a hit actually takes off, after resistances.
+/// Anything that gets through at all does at least 1.
+pub(crate) fn final_damage(
+    amount: f32,
+    kind: DamageType,
+    resistances: Option<&Resistances>,
+) -> u32 {
+    let amount = amount * resistances.map_or(1.0, |resistances| resistances.multiplier(kind));
+    if amount <= 0.0 {
+        0
+    } else {
+        (amount.round() as u32).max(1)
+    }
+}
+
+/// Takes every requested hit off its target's health, in the order they were
+/// asked for.
+pub(crate) fn apply_damage(
+    mut requests: EventReader<DamageRequest>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut targets: Query<(&mut Health, Option<&Resistances>)>,
+) {
+    for request in requests.iter() {
+        let (mut health, resistances) = match targets.get_mut(request.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if health.current == 0 {
+            continue;
+        }
+        // Only what the target had left counts, so overkill doesn't inflate
+        // anything tallied from the events.
+        let floor = if request.lethal { 0 } else { 1 };
+        let amount =
+            final_damage(request.amount, request.kind, resistances).min(health.current - floor);
+        if amount == 0 && !request.lethal {
+            continue;
+        }
+        health.current = (health.current - amount).min(health.max);
+        damage_events.send(DamageEvent {
+            source: request.source,
+            target: request.target,
+            owner: request.owner,
+            weapon: request.weapon.clone(),
+            amount,
+            kind: request.kind,
+            crit: request.crit,
+            fatal: health.current == 0,
+        });
+        if health.current == 0 {
+            death_events.send(DeathEvent {
+                entity: request.target,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+
+    fn resistances(kind: DamageType, amount: f32) -> Resistances {
+        Resistances(HashMap::from_iter([(kind, amount)]))
+    }
+
+    #[test]
+    fn resistances_scale_damage() {
+        let fire = resistances(DamageType::Fire, 0.5);
+        let immune = resistances(DamageType::Fire, 1.0);
+        let weak = resistances(DamageType::Fire, -0.5);
+        let cases = [
+            (10.0, DamageType::Fire, None, 10),
+            (10.0, DamageType::Fire, Some(&fire), 5),
+            (10.0, DamageType::Physical, Some(&fire), 10),
+            (10.0, DamageType::Fire, Some(&weak), 15),
+            // Anything that gets through does at least 1.
+            (1.0, DamageType::Fire, Some(&fire), 1),
+            (0.1, DamageType::Fire, None, 1),
+            // Full resistance, or nothing to begin with, does nothing.
+            (10.0, DamageType::Fire, Some(&immune), 0),
+            (0.0, DamageType::Fire, None, 0),
+        ];
+        for (amount, kind, resistances, expected) in cases {
+            assert_eq!(
+                final_damage(amount, kind, resistances),
+                expected,
+                "{} {:?} against {:?}",
+                amount,
+                kind,
+                resistances
+            );
+        }
+    }
+
+    /// Settles `hits` of physical damage, each with whether it's lethal, on
+    /// a target with `health`. Returns the health left, the amount and
+    /// fatality of every `DamageEvent`, and how many deaths were sent.
+    fn settle(
+        health: u32,
+        resistances: Option<Resistances>,
+        hits: &[(f32, bool)],
+    ) -> (u32, Vec<(u32, bool)>, usize) {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageRequest>>();
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<DeathEvent>>();
+        let mut target = world.spawn();
+        target.insert(Health {
+            max: health,
+            current: health,
+        });
+        if let Some(resistances) = resistances {
+            target.insert(resistances);
+        }
+        let target = target.id();
+        let mut requests = world.resource_mut::<Events<DamageRequest>>();
+        for &(amount, lethal) in hits {
+            requests.send(DamageRequest {
+                source: None,
+                target,
+                owner: None,
+                weapon: None,
+                amount,
+                kind: DamageType::Physical,
+                crit: false,
+                lethal,
+            });
+        }
+
+        SystemStage::single_threaded()
+            .with_system(apply_damage)
+            .run(&mut world);
+
+        let events = world.resource::<Events<DamageEvent>>();
+        let damage = events
+            .get_reader()
+            .iter(events)
+            .map(|event| (event.amount, event.fatal))
+            .collect();
+        let deaths = world.resource::<Events<DeathEvent>>();
+        let deaths = deaths.get_reader().iter(deaths).count();
+        (world.get::<Health>(target).unwrap().current, damage, deaths)
+    }
+
+    #[test]
+    fn non_lethal_damage_leaves_one_health() {
+        assert_eq!(
+            settle(5, None, &[(3.0, false), (3.0, false), (3.0, false)]),
+            (1, vec![(3, false), (1, false)], 0)
+        );
+    }
+
+    #[test]
+    fn only_the_killing_hit_is_fatal() {
+        assert_eq!(
+            settle(5, None, &[(3.0, true), (10.0, true), (10.0, true)]),
+            (0, vec![(3, false), (2, true)], 1)
+        );
+        assert_eq!(
+            settle(5, None, &[(1.0, false), (10.0, true)]),
+            (0, vec![(1, false), (4, true)], 1)
+        );
+    }
+
+    #[test]
+    fn resisted_damage_takes_nothing() {
+        let immune = resistances(DamageType::Physical, 1.0);
+        assert_eq!(
+            settle(5, Some(immune.clone()), &[(10.0, true)]),
+            (5, vec![(0, false)], 0)
+        );
+        assert_eq!(settle(5, Some(immune), &[(10.0, false)]), (5, vec![], 0));
+    }
+}
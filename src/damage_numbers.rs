@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::{
+    simulation::{Interpolated, SimulationApp, SimulationStage},
+    state::RunEntity,
+    DamageEvent, Lifetime, Player, SimulationClock, Velocity,
+};
+
+/// Most damage numbers on screen at once. Past this, the oldest one is reused.
+const MAX_DAMAGE_NUMBERS: usize = 100;
+/// Seconds a damage number stays up after the last hit it counts.
+const DAMAGE_NUMBER_LIFETIME: f32 = 0.8;
+/// Seconds after a hit during which further hits on the same target add to
+/// its number instead of showing a new one.
+const MERGE_WINDOW: f32 = 0.25;
+const RISE_SPEED: f32 = 30.0;
+/// How far above the target's center a number starts.
+const OFFSET: f32 = 12.0;
+/// Draws numbers over everything else in the world.
+const DAMAGE_NUMBER_Z: f32 = 10.0;
+
+/// The font damage numbers are drawn with, loaded with the HUD's.
+pub(crate) struct DamageNumberFont(pub Handle<Font>);
+
+/// Floating text showing damage dealt to `target`.
+#[derive(Component)]
+struct DamageNumber {
+    target: Entity,
+    amount: u32,
+    crit: bool,
+    to_player: bool,
+    merge_window: Timer,
+}
+
+impl DamageNumber {
+    fn style(&self, font: &Handle<Font>) -> TextStyle {
+        let (font_size, color) = if self.to_player {
+            (18.0, Color::rgb(1.0, 0.2, 0.2))
+        } else if self.crit {
+            (22.0, Color::rgb(1.0, 0.85, 0.0))
+        } else {
+            (14.0, Color::rgb(1.0, 1.0, 1.0))
+        };
+        TextStyle {
+            font: font.clone(),
+            font_size,
+            color,
+        }
+    }
+
+    fn text(&self) -> String {
+        if self.crit {
+            format!("{}!", self.amount)
+        } else {
+            self.amount.to_string()
+        }
+    }
+}
+
+fn lifetime() -> Lifetime {
+    Lifetime {
+        timer: Timer::new(Duration::from_secs_f32(DAMAGE_NUMBER_LIFETIME), false),
+    }
+}
+
+/// Shows a number for every hit, adding to the target's latest number if it's
+/// still open.
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    font: Res<DamageNumberFont>,
+    mut damage_events: EventReader<DamageEvent>,
+    targets: Query<(&Transform, Option<&Player>), Without<DamageNumber>>,
+    mut numbers: Query<(
+        Entity,
+        &mut DamageNumber,
+        &mut Text,
+        &mut Transform,
+        &mut Lifetime,
+        Option<&mut Interpolated>,
+    )>,
+) {
+    let mut live = numbers.iter().len();
+    // Numbers spawned here only show up in the query next tick, so hits on the
+    // same target in one tick are added up first.
+    let mut hits: Vec<(Entity, u32, bool)> = Vec::new();
+    for event in damage_events.iter().filter(|event| event.amount > 0) {
+        match hits.iter_mut().find(|(target, ..)| *target == event.target) {
+            Some((_, amount, crit)) => {
+                *amount += event.amount;
+                *crit |= event.crit;
+            }
+            None => hits.push((event.target, event.amount, event.crit)),
+        }
+    }
+
+    for (target, amount, crit) in hits {
+        let (target_transform, player) = match targets.get(target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        let translation = target_transform
+            .translation
+            .truncate()
+            .extend(DAMAGE_NUMBER_Z)
+            + Vec3::Y * OFFSET;
+
+        if let Some((_, mut number, mut text, _, mut lifetime, _)) = numbers
+            .iter_mut()
+            .find(|(_, number, ..)| number.target == target && !number.merge_window.finished())
+        {
+            number.amount += amount;
+            number.crit |= crit;
+            number.merge_window.reset();
+            *lifetime = self::lifetime();
+            text.sections[0].value = number.text();
+            text.sections[0].style = number.style(&font.0);
+            continue;
+        }
+
+        let number = DamageNumber {
+            target,
+            amount,
+            crit,
+            to_player: player.is_some(),
+            merge_window: Timer::new(Duration::from_secs_f32(MERGE_WINDOW), false),
+        };
+        let text = Text::with_section(
+            number.text(),
+            number.style(&font.0),
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            },
+        );
+
+        // `live` also counts numbers spawned earlier in this call, which
+        // aren't in the query until the commands are applied.
+        if live >= MAX_DAMAGE_NUMBERS {
+            // Numbers whose lifetime is up are already on their way out.
+            let oldest = numbers
+                .iter_mut()
+                .filter(|(_, _, _, _, lifetime, _)| !lifetime.timer.finished())
+                .max_by_key(|(_, _, _, _, lifetime, _)| lifetime.timer.elapsed())
+                .map(|(entity, ..)| entity);
+            if let Some(oldest) = oldest {
+                let (_, mut old_number, mut old_text, mut transform, mut lifetime, interpolated) =
+                    numbers.get_mut(oldest).unwrap();
+                *old_number = number;
+                *old_text = text;
+                transform.translation = translation;
+                if let Some(mut interpolated) = interpolated {
+                    interpolated.previous = translation;
+                }
+                *lifetime = self::lifetime();
+            }
+            // If every number is on its way out, this one is dropped.
+            continue;
+        }
+        live += 1;
+
+        commands
+            .spawn_bundle(Text2dBundle {
+                text,
+                transform: Transform::from_translation(translation),
+                ..default()
+            })
+            .insert(number)
+            .insert(lifetime())
+            .insert(Velocity {
+                speed: RISE_SPEED,
+                direction: Vec3::Y,
+            })
+            .insert(RunEntity);
+    }
+}
+
+/// Closes merge windows and fades numbers out over their lifetime.
+fn fade_damage_numbers(
+    clock: Res<SimulationClock>,
+    mut numbers: Query<(&mut DamageNumber, &mut Text, &Lifetime)>,
+) {
+    for (mut number, mut text, lifetime) in numbers.iter_mut() {
+        number.merge_window.tick(clock.delta());
+        let alpha = 1.0 - lifetime.timer.percent();
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+pub(crate) fn add_damage_numbers(app: &mut App) {
+    app.add_simulation_system_set(
+        SimulationStage::Cleanup,
+        SystemSet::new()
+            .with_system(spawn_damage_numbers)
+            .with_system(fade_damage_numbers.before(spawn_damage_numbers)),
+    );
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+
+/// Reads game data that ships built into the binary, then every `.ron` file in
+/// `assets/<dir>` on top of it, so that it can be tuned or extended without
+/// recompiling. Files that can't be parsed or fail `validate` are skipped with
+/// a warning. `kind` names the data in log messages.
+pub(crate) fn load_ron_definitions<T: DeserializeOwned>(
+    built_in: &[&str],
+    dir: &str,
+    kind: &str,
+    validate: impl Fn(&T) -> Result<(), String>,
+    mut insert: impl FnMut(T),
+) {
+    for source in built_in {
+        match ron::from_str(source)
+            .map_err(|err| err.to_string())
+            .and_then(|definition| validate(&definition).map(|()| definition))
+        {
+            Ok(definition) => insert(definition),
+            Err(err) => error!("Built-in {} is invalid: {}", kind, err),
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    load_dir(
+        &bevy::asset::FileAssetIo::get_root_path()
+            .join("assets")
+            .join(dir),
+        kind,
+        validate,
+        insert,
+    );
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_dir<T: DeserializeOwned>(
+    dir: &std::path::Path,
+    kind: &str,
+    validate: impl Fn(&T) -> Result<(), String>,
+    mut insert: impl FnMut(T),
+) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("Can't read {}s from {}: {}", kind, dir.display(), err);
+            return;
+        }
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension() == Some(std::ffi::OsStr::new("ron")))
+        .collect();
+    // Directory order varies between platforms; later files win on clashes.
+    paths.sort();
+    for path in paths {
+        match read_ron(&path).and_then(|definition| validate(&definition).map(|()| definition)) {
+            Ok(definition) => insert(definition),
+            Err(err) => warn!("Skipping {} {}: {}", kind, path.display(), err),
+        }
+    }
+}
+
+/// Reads a single piece of game data from `assets/<path>`, falling back to the
+/// copy built into the binary if the file is missing, can't be parsed or fails
+/// `validate`.
+pub(crate) fn load_ron_file<T: DeserializeOwned>(
+    built_in: &str,
+    path: &str,
+    kind: &str,
+    validate: impl Fn(&T) -> Result<(), String>,
+) -> T {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let path = bevy::asset::FileAssetIo::get_root_path()
+            .join("assets")
+            .join(path);
+        match read_ron(&path).and_then(|data| validate(&data).map(|()| data)) {
+            Ok(data) => return data,
+            Err(err) => warn!(
+                "Using the built-in {}, can't read {}: {}",
+                kind,
+                path.display(),
+                err
+            ),
+        }
+    }
+    ron::from_str(built_in)
+        .map_err(|err| err.to_string())
+        .and_then(|data| validate(&data).map(|()| data))
+        .unwrap_or_else(|err| panic!("Built-in {} is invalid: {}", kind, err))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_ron<T: DeserializeOwned>(path: &std::path::Path) -> Result<T, String> {
+    std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| ron::from_str(&source).map_err(|err| err.to_string()))
+}
@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+use crate::{
+    data, enemy, CameraView, Enemy, EnemyArchetypes, GameRng, Player, SimulationClock, Velocity,
+};
+
+/// How far outside the camera's view the spawn ring starts.
+const SPAWN_MARGIN: f32 = 30.0;
+/// How deep the spawn ring is, for shapes that scatter enemies across it.
+const SPAWN_DEPTH: f32 = 60.0;
+
+/// Where around the player a batch of enemies appears. Every shape sits on a
+/// ring just outside the camera's view.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+pub enum SpawnShape {
+    /// Scattered at random all around the ring.
+    #[default]
+    Ring,
+    /// Scattered across the part of the ring ahead of where the player is
+    /// heading. `spread` is the arc's width in degrees.
+    Arc { spread: f32 },
+    /// Evenly spaced around the whole ring, closing in from every side.
+    Circle,
+}
+
+/// Where each of `count` enemies in a batch spawns, relative to the center of
+/// the ring. `heading` is the direction the player is moving in, if any.
+fn spawn_offsets(
+    rng: &mut GameRng,
+    shape: SpawnShape,
+    radius: f32,
+    heading: Vec2,
+    count: usize,
+) -> Vec<Vec2> {
+    let on_ring = |angle: f32, radius: f32| Vec2::new(angle.cos(), angle.sin()) * radius;
+    match shape {
+        SpawnShape::Ring => (0..count)
+            .map(|_| {
+                let angle = rng.gen::<f32>() * TAU;
+                on_ring(angle, radius + rng.gen::<f32>() * SPAWN_DEPTH)
+            })
+            .collect(),
+        SpawnShape::Arc { spread } => {
+            let ahead = if heading == Vec2::ZERO {
+                rng.gen::<f32>() * TAU
+            } else {
+                heading.y.atan2(heading.x)
+            };
+            (0..count)
+                .map(|_| {
+                    let angle = ahead + (rng.gen::<f32>() - 0.5) * spread.to_radians();
+                    on_ring(angle, radius + rng.gen::<f32>() * SPAWN_DEPTH)
+                })
+                .collect()
+        }
+        SpawnShape::Circle => {
+            let start = rng.gen::<f32>() * TAU;
+            (0..count)
+                .map(|i| on_ring(start + i as f32 * TAU / count as f32, radius))
+                .collect()
+        }
+    }
+}
+
+/// A stretch of the run during which one archetype keeps spawning in batches.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Wave {
+    /// Seconds into the run of the first batch.
+    pub start: f32,
+    /// Seconds into the run after which no more batches spawn.
+    pub end: f32,
+    pub archetype: String,
+    /// Seconds between batches.
+    pub interval: f32,
+    /// Enemies per batch.
+    pub count: usize,
+    /// Multiplies the archetype's health.
+    #[serde(default = "default_multiplier")]
+    pub health: f32,
+    /// Multiplies the archetype's speed.
+    #[serde(default = "default_multiplier")]
+    pub speed: f32,
+    #[serde(default)]
+    pub shape: SpawnShape,
+}
+
+impl Wave {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval.is_nan() || self.interval <= 0.0 {
+            return Err(format!("interval must be positive, not {}", self.interval));
+        }
+        if self.count == 0 {
+            return Err("count must be at least 1".to_string());
+        }
+        if self.end < self.start {
+            return Err(format!(
+                "ends at {} before it starts at {}",
+                self.end, self.start
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn default_multiplier() -> f32 {
+    1.0
+}
+
+/// Which enemies spawn when over the course of a run, as described by
+/// `assets/waves.ron`. Waves may overlap.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WaveTimeline {
+    /// No more enemies spawn while this many are alive.
+    pub max_enemies: usize,
+    pub waves: Vec<Wave>,
+}
+
+const BUILT_IN_TIMELINE: &str = include_str!("../assets/waves.ron");
+
+impl WaveTimeline {
+    pub fn load() -> Self {
+        data::load_ron_file(
+            BUILT_IN_TIMELINE,
+            "waves.ron",
+            "wave timeline",
+            WaveTimeline::validate,
+        )
+    }
+
+    /// Checks that every wave can actually run.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, wave) in self.waves.iter().enumerate() {
+            wave.validate()
+                .map_err(|err| format!("wave {} ({}): {}", i, wave.archetype, err))?;
+        }
+        Ok(())
+    }
+}
+
+/// How far the current run has got through the `WaveTimeline`.
+#[derive(Debug, Default)]
+pub struct WaveDirector {
+    /// Batches spawned so far by each wave, in timeline order.
+    batches: Vec<u32>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_wave_director(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    timeline: Res<WaveTimeline>,
+    mut director: ResMut<WaveDirector>,
+    mut rng: ResMut<GameRng>,
+    archetypes: Res<EnemyArchetypes>,
+    view: Res<CameraView>,
+    enemies: Query<&Enemy>,
+    players: Query<&Velocity, With<Player>>,
+) {
+    let heading = players
+        .iter()
+        .next()
+        .map_or(Vec2::ZERO, |velocity| velocity.direction.truncate());
+    let center = view.center;
+    let radius = view.half_extents.length() + SPAWN_MARGIN;
+    let elapsed = clock.elapsed().as_secs_f32();
+    let mut alive = enemies.iter().len();
+    director.batches.resize(timeline.waves.len(), 0);
+    for (wave, batches) in timeline.waves.iter().zip(director.batches.iter_mut()) {
+        if elapsed < wave.start || elapsed >= wave.end {
+            continue;
+        }
+        let due = (((elapsed - wave.start) / wave.interval) as u32).saturating_add(1);
+        if *batches >= due {
+            continue;
+        }
+        // Batches missed while at the enemy cap are dropped, not caught up on.
+        *batches = due;
+        let archetype = match archetypes.get(&wave.archetype) {
+            Some(archetype) => archetype.scaled(wave.health, wave.speed),
+            None => {
+                warn!("No enemy archetype called {:?}", wave.archetype);
+                continue;
+            }
+        };
+        let amount = wave.count.min(timeline.max_enemies.saturating_sub(alive));
+        for offset in spawn_offsets(&mut rng, wave.shape, radius, heading, amount) {
+            enemy::spawn_enemy(&mut commands, &archetype, (center + offset).extend(1.0));
+        }
+        alive += amount;
+    }
+}
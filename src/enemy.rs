@@ -0,0 +1,253 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{
+    data, state::RunEntity, status, AppliesEffects, Blocked, Collider, CollisionLayers, Damage,
+    DamageType, DropExpOnDeath, Enemy, Health, Impulse, InvincibilityWindow, KnockbackResistance,
+    Mass, Name, Owner, PreventOverlap, Resistances, Solid, StatusEffect, StatusEffects, Velocity,
+};
+
+/// What an enemy does, beyond having health and hurting the player on contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BehaviorTag {
+    /// Walks straight at the player.
+    Chase,
+    /// Can't overlap other solid bodies, and gets pushed out of them.
+    Solid,
+}
+
+/// One kind of enemy, as described by a file in `assets/enemies`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyArchetype {
+    pub id: String,
+    pub health: u32,
+    pub damage: u32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    pub speed: f32,
+    pub size: (f32, f32),
+    pub color: (f32, f32, f32),
+    pub experience: u32,
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    #[serde(default)]
+    pub behaviors: Vec<BehaviorTag>,
+    /// How much of each type of damage this enemy shrugs off. See
+    /// `Resistances`.
+    #[serde(default)]
+    pub resistances: HashMap<DamageType, f32>,
+    /// How much knockback this enemy shrugs off, from 0 to 1.
+    #[serde(default)]
+    pub knockback_resistance: f32,
+    /// Status effects that touching this enemy applies.
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>,
+}
+
+impl EnemyArchetype {
+    /// A copy with health and speed multiplied, for tougher enemies later in
+    /// a run. Health never drops below 1.
+    pub fn scaled(&self, health: f32, speed: f32) -> Self {
+        EnemyArchetype {
+            health: ((self.health as f32 * health).round() as u32).max(1),
+            speed: self.speed * speed,
+            ..self.clone()
+        }
+    }
+
+    /// Checks that enemies of this kind can be spawned and pushed around.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.health < 1 {
+            return Err("health must be at least 1".to_string());
+        }
+        if self.size.0.is_nan() || self.size.1.is_nan() || self.size.0 <= 0.0 || self.size.1 <= 0.0
+        {
+            return Err(format!("size must be positive, not {:?}", self.size));
+        }
+        // An infinite mass is fine; it's never pushed.
+        if self.mass.is_nan() || self.mass <= 0.0 {
+            return Err(format!("mass must be positive, not {}", self.mass));
+        }
+        status::validate_effects(&self.effects)
+    }
+}
+
+fn default_mass() -> f32 {
+    1.0
+}
+
+/// Moves towards the player every tick. See `enemy_ai`.
+#[derive(Component)]
+pub(crate) struct ChasePlayer;
+
+/// Every enemy archetype, by ID.
+///
+/// The archetypes that ship with the game are built in, and any `.ron` file
+/// in `assets/enemies` is read on startup on top of them, so enemies can be
+/// tuned or added without recompiling.
+#[derive(Default)]
+pub struct EnemyArchetypes {
+    archetypes: HashMap<String, EnemyArchetype>,
+}
+
+const BUILT_IN_ARCHETYPES: &[&str] = &[
+    include_str!("../assets/enemies/grunt.ron"),
+    include_str!("../assets/enemies/runner.ron"),
+    include_str!("../assets/enemies/brute.ron"),
+];
+
+impl EnemyArchetypes {
+    pub fn load() -> Self {
+        let mut archetypes = EnemyArchetypes::default();
+        data::load_ron_definitions(
+            BUILT_IN_ARCHETYPES,
+            "enemies",
+            "enemy archetype",
+            EnemyArchetype::validate,
+            |archetype| archetypes.insert(archetype),
+        );
+        archetypes
+    }
+
+    pub fn insert(&mut self, archetype: EnemyArchetype) {
+        self.archetypes.insert(archetype.id.clone(), archetype);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EnemyArchetype> {
+        self.archetypes.get(id)
+    }
+}
+
+pub(crate) fn spawn_enemy(commands: &mut Commands, archetype: &EnemyArchetype, translation: Vec3) {
+    let size = Vec2::new(archetype.size.0, archetype.size.1);
+    let mut enemy = commands.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            color: Color::rgb(archetype.color.0, archetype.color.1, archetype.color.2),
+            ..default()
+        },
+        transform: Transform {
+            translation,
+            scale: size.extend(1.0),
+            ..default()
+        },
+        ..default()
+    });
+    enemy
+        .insert(Name(archetype.id.clone()))
+        .insert(Health {
+            max: archetype.health,
+            current: archetype.health,
+        })
+        .insert(Damage::new(archetype.damage, archetype.damage_type))
+        .insert(Velocity {
+            speed: archetype.speed,
+            direction: Vec3::ZERO,
+        })
+        .insert(Owner(None))
+        .insert(DropExpOnDeath {
+            amount: archetype.experience,
+        })
+        .insert(Collider::aabb(size))
+        .insert(CollisionLayers::new(
+            CollisionLayers::ENEMY,
+            CollisionLayers::PLAYER,
+        ))
+        .insert(InvincibilityWindow {
+            damage_sources: HashMap::new(),
+        })
+        .insert(StatusEffects::default())
+        .insert(Impulse::default())
+        .insert(RunEntity)
+        .insert(Enemy);
+    if !archetype.resistances.is_empty() {
+        enemy.insert(Resistances(archetype.resistances.clone()));
+    }
+    if archetype.knockback_resistance > 0.0 {
+        enemy.insert(KnockbackResistance(archetype.knockback_resistance));
+    }
+    if !archetype.effects.is_empty() {
+        enemy.insert(AppliesEffects(archetype.effects.clone()));
+    }
+    for behavior in &archetype.behaviors {
+        match behavior {
+            BehaviorTag::Chase => {
+                enemy.insert(ChasePlayer);
+            }
+            BehaviorTag::Solid => {
+                enemy
+                    .insert(PreventOverlap)
+                    .insert(Solid)
+                    .insert(Mass(archetype.mass))
+                    .insert(Blocked::default());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built_in() -> Vec<EnemyArchetype> {
+        BUILT_IN_ARCHETYPES
+            .iter()
+            .map(|source| ron::from_str(source).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn built_in_archetypes_are_valid() {
+        for archetype in built_in() {
+            assert_eq!(archetype.validate(), Ok(()), "{}", archetype.id);
+        }
+    }
+
+    #[test]
+    fn bad_archetypes_are_rejected() {
+        let grunt = built_in().remove(0);
+        let immovable = EnemyArchetype {
+            mass: f32::INFINITY,
+            ..grunt.clone()
+        };
+        assert_eq!(immovable.validate(), Ok(()));
+        for bad in [
+            EnemyArchetype {
+                mass: 0.0,
+                ..grunt.clone()
+            },
+            EnemyArchetype {
+                mass: -1.0,
+                ..grunt.clone()
+            },
+            EnemyArchetype {
+                mass: f32::NAN,
+                ..grunt.clone()
+            },
+            EnemyArchetype {
+                size: (0.0, 10.0),
+                ..grunt.clone()
+            },
+            EnemyArchetype {
+                size: (10.0, -1.0),
+                ..grunt.clone()
+            },
+            EnemyArchetype {
+                health: 0,
+                ..grunt.clone()
+            },
+            EnemyArchetype {
+                effects: vec![StatusEffect {
+                    kind: crate::StatusKind::Slow,
+                    duration: -1.0,
+                    interval: 1.0,
+                    strength: 0.5,
+                    stacking: Default::default(),
+                    immunity: 0.0,
+                }],
+                ..grunt.clone()
+            },
+        ] {
+            assert!(bad.validate().is_err(), "{:?} was accepted", bad);
+        }
+    }
+}
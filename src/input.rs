@@ -0,0 +1,489 @@
+use bevy::{input::InputSystem, prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::GameState;
+
+/// Something the player can do, independent of which key or button does it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Pause,
+    Confirm,
+    Dash,
+    /// Picks an upgrade on the level-up screen straight away.
+    Choose1,
+    Choose2,
+    Choose3,
+    Choose4,
+}
+
+impl Action {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Dash => "Dash",
+            Action::Choose1 => "Upgrade 1",
+            Action::Choose2 => "Upgrade 2",
+            Action::Choose3 => "Upgrade 3",
+            Action::Choose4 => "Upgrade 4",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+/// Which keys and buttons trigger each action, as read from and written to
+/// `bindings.ron`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub keyboard: BTreeMap<Action, Vec<KeyCode>>,
+    pub gamepad: BTreeMap<Action, Vec<GamepadButtonType>>,
+    /// The stick that moves the player, on top of the move actions.
+    pub move_stick: Stick,
+    /// How far the stick has to be pushed, from 0 to 1, before it counts.
+    pub deadzone: f32,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use GamepadButtonType::*;
+        InputBindings {
+            keyboard: BTreeMap::from([
+                (Action::MoveUp, vec![KeyCode::Up, KeyCode::W]),
+                (Action::MoveDown, vec![KeyCode::Down, KeyCode::S]),
+                (Action::MoveLeft, vec![KeyCode::Left, KeyCode::A]),
+                (Action::MoveRight, vec![KeyCode::Right, KeyCode::D]),
+                (Action::Pause, vec![KeyCode::P]),
+                (Action::Confirm, vec![KeyCode::Return]),
+                (Action::Dash, vec![KeyCode::Space, KeyCode::LShift]),
+                (Action::Choose1, vec![KeyCode::Key1, KeyCode::Numpad1]),
+                (Action::Choose2, vec![KeyCode::Key2, KeyCode::Numpad2]),
+                (Action::Choose3, vec![KeyCode::Key3, KeyCode::Numpad3]),
+                (Action::Choose4, vec![KeyCode::Key4, KeyCode::Numpad4]),
+            ]),
+            gamepad: BTreeMap::from([
+                (Action::MoveUp, vec![DPadUp]),
+                (Action::MoveDown, vec![DPadDown]),
+                (Action::MoveLeft, vec![DPadLeft]),
+                (Action::MoveRight, vec![DPadRight]),
+                (Action::Pause, vec![Start]),
+                (Action::Confirm, vec![South]),
+                (Action::Dash, vec![East, RightTrigger]),
+            ]),
+            move_stick: Stick::Left,
+            deadzone: 0.2,
+        }
+    }
+}
+
+impl InputBindings {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path() -> std::path::PathBuf {
+        bevy::asset::FileAssetIo::get_root_path().join("bindings.ron")
+    }
+
+    /// Reads the bindings file, or writes out the defaults if there isn't one
+    /// yet so that they can be edited.
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = Self::path();
+            match std::fs::read_to_string(&path) {
+                Ok(source) => match ron::from_str::<InputBindings>(&source) {
+                    Ok(mut bindings) => {
+                        // Files saved before an action existed don't mention it.
+                        let defaults = InputBindings::default();
+                        for (action, keys) in defaults.keyboard {
+                            bindings.keyboard.entry(action).or_insert(keys);
+                        }
+                        for (action, buttons) in defaults.gamepad {
+                            bindings.gamepad.entry(action).or_insert(buttons);
+                        }
+                        return bindings;
+                    }
+                    Err(err) => warn!(
+                        "Using default bindings, {} is invalid: {}",
+                        path.display(),
+                        err
+                    ),
+                },
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    InputBindings::default().save();
+                }
+                Err(err) => warn!(
+                    "Using default bindings, can't read {}: {}",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        InputBindings::default()
+    }
+
+    pub fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = Self::path();
+            let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string())
+                .and_then(|source| std::fs::write(&path, source).map_err(|err| err.to_string()));
+            if let Err(err) = result {
+                warn!("Can't save bindings to {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    /// The keys bound to an action, for showing on screen.
+    pub fn describe_keys(&self, action: Action) -> String {
+        let keys = self.keyboard.get(&action).map_or(&[][..], Vec::as_slice);
+        if keys.is_empty() {
+            return "unbound".to_string();
+        }
+        keys.iter()
+            .map(|key| format!("{:?}", key))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The keys and buttons bound to an action, for showing on screen.
+    pub fn describe_inputs(&self, action: Action) -> String {
+        let buttons = self.gamepad.get(&action).map_or(&[][..], Vec::as_slice);
+        if buttons.is_empty() {
+            return self.describe_keys(action);
+        }
+        let buttons = buttons
+            .iter()
+            .map(|button| format!("{:?}", button))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{} / {}", self.describe_keys(action), buttons)
+    }
+
+    /// Makes `key` the main key for an action, keeping any others. Refuses a
+    /// key that already belongs to another action, and returns that action.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) -> Result<(), Action> {
+        if let Some((&other, _)) = self
+            .keyboard
+            .iter()
+            .find(|(&other, keys)| other != action && keys.contains(&key))
+        {
+            return Err(other);
+        }
+        let keys = self.keyboard.entry(action).or_default();
+        keys.retain(|&bound| bound != key);
+        keys.insert(0, key);
+        Ok(())
+    }
+}
+
+/// What the player is asking for this frame, whatever device they're on.
+#[derive(Debug, Default)]
+pub struct ActionState {
+    movement: Vec2,
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    /// Presses not yet taken by gameplay. See `take`.
+    latched: HashSet<Action>,
+}
+
+impl ActionState {
+    /// Which way to move and how fast, as a vector no longer than 1.
+    pub fn movement(&self) -> Vec2 {
+        self.movement
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Whether the action was pressed since the last time this was asked.
+    /// Gameplay runs on simulation ticks rather than frames, and would
+    /// otherwise miss presses or see them more than once.
+    pub fn take(&mut self, action: Action) -> bool {
+        self.latched.remove(&action)
+    }
+}
+
+/// Scales a stick so that it reads zero inside the deadzone and still reaches
+/// full strength at the edge.
+fn apply_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone || deadzone >= 1.0 {
+        return Vec2::ZERO;
+    }
+    stick / length * ((length - deadzone) / (1.0 - deadzone)).min(1.0)
+}
+
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    state: Res<State<GameState>>,
+    mut actions: ResMut<ActionState>,
+) {
+    actions.pressed.clear();
+    actions.just_pressed.clear();
+    for (&action, bound) in &bindings.keyboard {
+        if keys.any_pressed(bound.iter().copied()) {
+            actions.pressed.insert(action);
+        }
+        if keys.any_just_pressed(bound.iter().copied()) {
+            actions.just_pressed.insert(action);
+        }
+    }
+    let (stick_x, stick_y) = match bindings.move_stick {
+        Stick::Left => (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+        Stick::Right => (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+    };
+    let mut stick = Vec2::ZERO;
+    for &gamepad in gamepads.iter() {
+        for (&action, bound) in &bindings.gamepad {
+            let buttons_of = |&button: &GamepadButtonType| GamepadButton(gamepad, button);
+            if buttons.any_pressed(bound.iter().map(buttons_of)) {
+                actions.pressed.insert(action);
+            }
+            if buttons.any_just_pressed(bound.iter().map(buttons_of)) {
+                actions.just_pressed.insert(action);
+            }
+        }
+        stick += apply_deadzone(
+            Vec2::new(
+                axes.get(GamepadAxis(gamepad, stick_x)).unwrap_or(0.0),
+                axes.get(GamepadAxis(gamepad, stick_y)).unwrap_or(0.0),
+            ),
+            bindings.deadzone,
+        );
+    }
+
+    let mut digital = Vec2::ZERO;
+    for (action, direction) in [
+        (Action::MoveUp, Vec2::Y),
+        (Action::MoveDown, -Vec2::Y),
+        (Action::MoveLeft, -Vec2::X),
+        (Action::MoveRight, Vec2::X),
+    ] {
+        if actions.pressed(action) {
+            digital += direction;
+        }
+    }
+    actions.movement = (digital.normalize_or_zero() + stick).clamp_length_max(1.0);
+
+    if *state.current() == GameState::Playing {
+        let just_pressed = actions.just_pressed.clone();
+        actions.latched.extend(just_pressed);
+    } else {
+        // Don't carry presses from menus over into gameplay.
+        actions.latched.clear();
+    }
+}
+
+/// The actions that can be rebound from the pause screen, in the order they
+/// are listed there.
+pub(crate) const REBINDABLE: [Action; 5] = [
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::Dash,
+];
+
+const REBIND_KEYS: [KeyCode; 5] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+];
+
+/// The action waiting for a new key on the pause screen, if any.
+#[derive(Debug, Default)]
+pub struct Rebinding {
+    pub action: Option<Action>,
+    /// The last key refused because another action already had it.
+    pub conflict: Option<(KeyCode, Action)>,
+}
+
+fn rebind_keys(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    if *state.current() != GameState::Paused {
+        if rebinding.action.is_some() || rebinding.conflict.is_some() {
+            rebinding.action = None;
+            rebinding.conflict = None;
+        }
+        return;
+    }
+    match rebinding.action {
+        Some(action) => {
+            if let Some(&key) = keys.get_just_pressed().next() {
+                match bindings.rebind(action, key) {
+                    Ok(()) => {
+                        bindings.save();
+                        rebinding.conflict = None;
+                    }
+                    Err(other) => rebinding.conflict = Some((key, other)),
+                }
+                rebinding.action = None;
+            }
+        }
+        None => {
+            if let Some((_, &action)) = REBIND_KEYS
+                .iter()
+                .zip(&REBINDABLE)
+                .find(|(&key, _)| keys.just_pressed(key))
+            {
+                rebinding.action = Some(action);
+                rebinding.conflict = None;
+            }
+        }
+    }
+}
+
+/// Reading actions from the keyboard and gamepads, and rebinding keys while
+/// paused.
+pub(crate) fn add_input_actions(app: &mut App) {
+    app.insert_resource(InputBindings::load())
+        .init_resource::<Rebinding>()
+        .add_system_to_stage(CoreStage::PreUpdate, update_action_state.after(InputSystem))
+        .add_system_to_stage(CoreStage::PreUpdate, rebind_keys.after(InputSystem));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_refuses_keys_in_use() {
+        let mut bindings = InputBindings::default();
+        assert_eq!(bindings.rebind(Action::MoveUp, KeyCode::W), Ok(()));
+        assert_eq!(
+            bindings.keyboard[&Action::MoveUp],
+            [KeyCode::W, KeyCode::Up]
+        );
+
+        assert_eq!(
+            bindings.rebind(Action::MoveDown, KeyCode::W),
+            Err(Action::MoveUp)
+        );
+        assert_eq!(
+            bindings.keyboard[&Action::MoveDown],
+            [KeyCode::Down, KeyCode::S]
+        );
+        assert_eq!(
+            bindings.keyboard[&Action::MoveUp],
+            [KeyCode::W, KeyCode::Up]
+        );
+
+        assert_eq!(bindings.rebind(Action::Dash, KeyCode::Q), Ok(()));
+        assert_eq!(
+            bindings.keyboard[&Action::Dash],
+            [KeyCode::Q, KeyCode::Space, KeyCode::LShift]
+        );
+        assert_eq!(bindings.rebind(Action::Dash, KeyCode::Q), Ok(()));
+        assert_eq!(bindings.keyboard[&Action::Dash].len(), 3);
+    }
+
+    #[test]
+    fn deadzone_edges() {
+        let cases = [
+            (Vec2::ZERO, Vec2::ZERO),
+            (Vec2::new(0.1, 0.0), Vec2::ZERO),
+            // Exactly at the deadzone still reads zero.
+            (Vec2::new(0.0, -0.2), Vec2::ZERO),
+            (Vec2::new(0.6, 0.0), Vec2::new(0.5, 0.0)),
+            (Vec2::new(-1.0, 0.0), Vec2::new(-1.0, 0.0)),
+            // Diagonals are rescaled by length, keeping their direction.
+            (Vec2::new(0.36, 0.48), Vec2::new(0.3, 0.4)),
+            (Vec2::new(0.6, 0.8), Vec2::new(0.6, 0.8)),
+            // Sticks that read past their edge are held to full strength.
+            (Vec2::new(1.0, 1.0), Vec2::ONE.normalize()),
+        ];
+        for (stick, expected) in cases {
+            let result = apply_deadzone(stick, 0.2);
+            assert!(
+                result.abs_diff_eq(expected, 1e-5),
+                "{:?} read as {:?}, not {:?}",
+                stick,
+                result,
+                expected
+            );
+        }
+        assert_eq!(apply_deadzone(Vec2::X, 1.0), Vec2::ZERO);
+    }
+
+    fn input_world(state: GameState) -> World {
+        let mut world = World::new();
+        world.insert_resource(InputBindings::default());
+        world.insert_resource(Input::<KeyCode>::default());
+        world.insert_resource(Input::<GamepadButton>::default());
+        world.insert_resource(Axis::<GamepadAxis>::default());
+        world.insert_resource(Gamepads::default());
+        world.insert_resource(State::new(state));
+        world.insert_resource(ActionState::default());
+        world
+    }
+
+    /// Runs a frame with `change` made to the keyboard first.
+    fn frame(world: &mut World, change: impl FnOnce(&mut Input<KeyCode>)) {
+        let mut keys = world.resource_mut::<Input<KeyCode>>();
+        keys.clear();
+        change(&mut keys);
+        SystemStage::single_threaded()
+            .with_system(update_action_state)
+            .run(world);
+    }
+
+    #[test]
+    fn presses_are_taken_once() {
+        let mut world = input_world(GameState::Playing);
+        frame(&mut world, |keys| keys.press(KeyCode::Space));
+        // Held over a frame with no tick in it.
+        frame(&mut world, |_| {});
+        let mut actions = world.resource_mut::<ActionState>();
+        assert!(actions.pressed(Action::Dash));
+        assert!(!actions.just_pressed(Action::Dash));
+        assert!(actions.take(Action::Dash));
+        assert!(!actions.take(Action::Dash));
+        assert!(!actions.take(Action::Pause));
+
+        // Released and pressed again before a tick takes it.
+        frame(&mut world, |keys| keys.release(KeyCode::Space));
+        frame(&mut world, |keys| keys.press(KeyCode::LShift));
+        frame(&mut world, |keys| keys.release(KeyCode::LShift));
+        let mut actions = world.resource_mut::<ActionState>();
+        assert!(!actions.pressed(Action::Dash));
+        assert!(actions.take(Action::Dash));
+        assert!(!actions.take(Action::Dash));
+    }
+
+    #[test]
+    fn presses_outside_play_are_not_latched() {
+        let mut world = input_world(GameState::Paused);
+        frame(&mut world, |keys| keys.press(KeyCode::Space));
+        let mut actions = world.resource_mut::<ActionState>();
+        assert!(actions.just_pressed(Action::Dash));
+        assert!(!actions.take(Action::Dash));
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+
+use crate::SimulationClock;
+
+/// How quickly impulses die down, as the rate at which they shrink per
+/// second.
+const IMPULSE_DECAY: f32 = 10.0;
+/// Impulses slower than this stop altogether.
+const IMPULSE_REST_SPEED: f32 = 1.0;
+
+/// Pushes whatever this entity hits along its direction of travel, starting
+/// at `force` units per second.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Knockback {
+    pub force: f32,
+}
+
+/// How much knockback an entity shrugs off, from 0 (none) to 1 (all of it).
+#[derive(Component, Debug, Clone, Copy)]
+pub struct KnockbackResistance(pub f32);
+
+/// Movement from being knocked back, on top of the entity's own `Velocity`,
+/// which AI and input overwrite every tick. Entities without this component
+/// can't be knocked back.
+#[derive(Component, Debug, Default)]
+pub struct Impulse {
+    pub velocity: Vec3,
+}
+
+impl Impulse {
+    pub fn push(&mut self, direction: Vec3, force: f32, resistance: Option<&KnockbackResistance>) {
+        let resistance = resistance.map_or(0.0, |resistance| resistance.0.clamp(0.0, 1.0));
+        self.velocity += direction.normalize_or_zero() * force * (1.0 - resistance);
+    }
+}
+
+pub(crate) fn decay_impulses(clock: Res<SimulationClock>, mut query: Query<&mut Impulse>) {
+    let decay = (-IMPULSE_DECAY * clock.delta_seconds()).exp();
+    for mut impulse in query.iter_mut() {
+        if impulse.velocity == Vec3::ZERO {
+            continue;
+        }
+        impulse.velocity *= decay;
+        if impulse.velocity.length_squared() < IMPULSE_REST_SPEED * IMPULSE_REST_SPEED {
+            impulse.velocity = Vec3::ZERO;
+        }
+    }
+}
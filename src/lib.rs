@@ -0,0 +1,1317 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use simulation::{SimulationApp, SimulationStage};
+
+mod arena;
+mod camera;
+mod collision;
+mod damage;
+mod damage_numbers;
+mod data;
+mod director;
+mod enemy;
+mod input;
+mod knockback;
+mod simulation;
+mod spatial;
+mod state;
+mod status;
+mod upgrade;
+mod weapon;
+
+pub use arena::ArenaBounds;
+pub use camera::{CameraFollow, CameraView};
+pub use collision::{Blocked, Collider, ColliderShape, CollisionLayers, Contact, Mass, Sweep};
+pub use damage::{DamageEvent, DamageRequest, DamageType, Resistances};
+pub use director::{SpawnShape, Wave, WaveDirector, WaveTimeline};
+pub use enemy::{BehaviorTag, EnemyArchetype, EnemyArchetypes};
+pub use input::{Action, ActionState, InputBindings, Rebinding, Stick};
+pub use knockback::{Impulse, Knockback, KnockbackResistance};
+pub use simulation::SimulationClock;
+pub use spatial::SpatialHash;
+use state::RunEntity;
+pub use state::{GameState, RunStats, WeaponTally};
+pub use status::{AppliesEffects, Stacking, StatusEffect, StatusEffects, StatusKind};
+pub use upgrade::{
+    experience_for_level, AutoPickUpgrades, Level, PassiveStat, Upgrade, UpgradeChoice,
+};
+pub use weapon::{
+    FiredFrom, ProjectileTemplate, Targeting, Weapon, WeaponDefinition, WeaponDefinitions,
+    WeaponStats, Weapons,
+};
+
+const PLAYER_COLOR: Color = Color::rgb(0.0, 0.0, 1.0);
+
+/// Runs the game in a window, with rendering, UI and keyboard input.
+pub struct GamePlugin;
+
+/// Every gameplay system, without anything that needs a window, a renderer or
+/// the asset server. `GamePlugin` builds on top of this.
+pub struct SimulationPlugin;
+
+/// Sent when `collider` overlaps `obstacle` and the collider's layers are
+/// interested in the obstacle's. See `CollisionLayers`.
+pub struct CollisionEvent {
+    collider: Entity,
+    obstacle: Entity,
+}
+
+pub struct DeathEvent {
+    entity: Entity,
+}
+
+/// The one source of randomness for gameplay. Every random decision must draw
+/// from this so that a run can be reproduced from its seed.
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(rand::thread_rng().gen())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// The text showing health, score and seed during play.
+#[derive(Component)]
+struct HudText;
+
+#[derive(Component)]
+struct Owner(Option<Entity>);
+
+#[derive(Component)]
+struct Name(String);
+
+#[derive(Component)]
+struct Player;
+
+#[derive(Component)]
+struct Attractable;
+
+#[derive(Component)]
+struct Attraction {
+    radius: f32,
+    force: f32,
+}
+
+#[derive(Component)]
+struct Experience {
+    amount: u32,
+}
+
+#[derive(Component)]
+struct Enemy;
+
+#[derive(Component)]
+struct PreventOverlap;
+
+#[derive(Component)]
+struct Solid;
+
+/// A short burst of extra speed on top of `Velocity`, on a cooldown.
+#[derive(Component)]
+struct Dash {
+    speed: f32,
+    duration: std::time::Duration,
+    cooldown: std::time::Duration,
+    direction: Vec3,
+    remaining: std::time::Duration,
+    ready_in: std::time::Duration,
+}
+
+impl Dash {
+    fn new(speed: f32, duration: std::time::Duration, cooldown: std::time::Duration) -> Self {
+        Dash {
+            speed,
+            duration,
+            cooldown,
+            direction: Vec3::ZERO,
+            remaining: std::time::Duration::ZERO,
+            ready_in: std::time::Duration::ZERO,
+        }
+    }
+
+    fn tick(&mut self, delta: std::time::Duration) {
+        self.remaining = self.remaining.saturating_sub(delta);
+        self.ready_in = self.ready_in.saturating_sub(delta);
+    }
+
+    /// Dashes in `direction`, unless still cooling down from the last dash.
+    fn start(&mut self, direction: Vec3) {
+        if self.ready_in.is_zero() {
+            self.direction = direction;
+            self.remaining = self.duration;
+            self.ready_in = self.cooldown;
+        }
+    }
+
+    fn is_dashing(&self) -> bool {
+        !self.remaining.is_zero()
+    }
+}
+
+#[derive(Component)]
+struct Velocity {
+    speed: f32,
+    direction: Vec3,
+}
+
+#[derive(Component)]
+struct Health {
+    max: u32,
+    current: u32,
+}
+
+/// What touching this entity does to health. Every hit rolls its own variance
+/// and crit.
+#[derive(Component)]
+struct Damage {
+    damage: u32,
+    kind: DamageType,
+    /// How far a hit can land either side of `damage`, as a fraction of it.
+    variance: f32,
+    crit_chance: f32,
+    crit_multiplier: f32,
+}
+
+impl Damage {
+    fn new(damage: u32, kind: DamageType) -> Self {
+        Damage {
+            damage,
+            kind,
+            variance: 0.0,
+            crit_chance: 0.0,
+            crit_multiplier: 1.0,
+        }
+    }
+
+    /// How much one hit deals before resistances, and whether it was a crit.
+    /// Only draws from the RNG for the rolls this damage actually has.
+    fn roll(&self, rng: &mut GameRng) -> (f32, bool) {
+        let mut amount = self.damage as f32;
+        if self.variance > 0.0 {
+            amount *= 1.0 + rng.gen_range(-self.variance..=self.variance);
+        }
+        let crit = self.crit_chance > 0.0 && rng.gen::<f32>() < self.crit_chance;
+        if crit {
+            amount *= self.crit_multiplier;
+        }
+        (amount, crit)
+    }
+}
+
+/// Extra crit chance and crit multiplier the player adds to every weapon.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct CritBonus {
+    pub chance: f32,
+    pub multiplier: f32,
+}
+
+#[derive(Component)]
+struct Lifetime {
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct Punchthrough {
+    amount: u32,
+}
+
+#[derive(Component)]
+struct Bullet;
+
+#[derive(Component)]
+struct Pickup;
+
+#[derive(Component)]
+struct DropExpOnDeath {
+    amount: u32,
+}
+
+#[derive(Component)]
+struct InvincibilityWindow {
+    damage_sources: HashMap<Entity, Timer>,
+}
+
+fn spawn_exp_drop(commands: &mut Commands, translation: Vec3, value: u32) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(0.0, 1.0, 0.0),
+                ..default()
+            },
+            transform: Transform {
+                translation,
+                scale: Vec3::new(3.0, 3.0, 1.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Velocity {
+            speed: 0.0,
+            direction: Vec3::ZERO,
+        })
+        .insert(Lifetime {
+            timer: Timer::new(std::time::Duration::from_secs(30), false),
+        })
+        .insert(Experience { amount: value })
+        .insert(Attractable)
+        .insert(RunEntity)
+        .insert(Collider::circle(1.5))
+        .insert(CollisionLayers::new(
+            CollisionLayers::PICKUP,
+            CollisionLayers::NONE,
+        ))
+        .insert(Pickup);
+}
+
+fn setup_health_display(mut commands: Commands, asset_server: Res<AssetServer>, rng: Res<GameRng>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands.insert_resource(damage_numbers::DamageNumberFont(font.clone()));
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "Health: ".to_string(),
+                        style: TextStyle {
+                            font: font.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: font.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                        },
+                    },
+                    TextSection {
+                        value: "  Score: ".to_string(),
+                        style: TextStyle {
+                            font: font.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                        },
+                    },
+                    TextSection {
+                        value: "0".to_string(),
+                        style: TextStyle {
+                            font: font.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                        },
+                    },
+                    TextSection {
+                        value: format!("  Seed: {}", rng.seed()),
+                        style: TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.6, 0.6, 0.6),
+                        },
+                    },
+                ],
+                ..default()
+            },
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HudText);
+}
+
+// spawn player system
+fn setup(mut commands: Commands, definitions: Res<WeaponDefinitions>) {
+    let mut weapons = Weapons::default();
+    match definitions.get("bullet") {
+        Some(definition) => weapons.add(Weapon::new(definition.clone())),
+        None => warn!("No weapon definition for \"bullet\""),
+    }
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: PLAYER_COLOR,
+                ..default()
+            },
+            transform: Transform {
+                scale: Vec3::new(10.0, 10.0, 1.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Name(String::from("Player")))
+        .insert(Health {
+            max: 100,
+            current: 100,
+        })
+        .insert(Experience { amount: 0 })
+        .insert(Level::default())
+        .insert(CritBonus::default())
+        .insert(StatusEffects::default())
+        .insert(Dash::new(
+            300.0,
+            std::time::Duration::from_millis(150),
+            std::time::Duration::from_secs(1),
+        ))
+        .insert(Velocity {
+            speed: 80.0,
+            direction: Vec3::ZERO,
+        })
+        .insert(weapons)
+        .insert(InvincibilityWindow {
+            damage_sources: HashMap::new(),
+        })
+        .insert(PreventOverlap)
+        .insert(Mass(f32::INFINITY))
+        .insert(Solid)
+        .insert(Collider::aabb(Vec2::new(10.0, 10.0)))
+        .insert(CollisionLayers::new(
+            CollisionLayers::PLAYER,
+            CollisionLayers::PICKUP,
+        ))
+        .insert(RunEntity)
+        .insert(Player);
+
+    //spawn_enemies(commands, 100);
+}
+
+fn handle_input(
+    clock: Res<SimulationClock>,
+    mut actions: ResMut<ActionState>,
+    mut query: Query<(&mut Velocity, Option<&mut Dash>, Option<&StatusEffects>), With<Player>>,
+) {
+    let movement = actions.movement().extend(0.0);
+    let dash_pressed = actions.take(Action::Dash);
+    for (mut velocity, dash, effects) in query.iter_mut() {
+        velocity.direction = movement;
+        let frozen = effects.is_some_and(StatusEffects::is_frozen);
+        if let Some(mut dash) = dash {
+            dash.tick(clock.delta());
+            if dash_pressed && movement != Vec3::ZERO && !frozen {
+                dash.start(movement.normalize());
+            }
+        }
+    }
+}
+
+fn move_dashing(clock: Res<SimulationClock>, mut query: Query<(&mut Transform, &Dash)>) {
+    for (mut transform, dash) in query.iter_mut() {
+        if dash.is_dashing() {
+            transform.translation += dash.direction * dash.speed * clock.delta_seconds();
+        }
+    }
+}
+
+fn enemy_ai(
+    mut query: Query<(&mut Velocity, &Transform), With<enemy::ChasePlayer>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    for player in player_query.iter() {
+        for (mut velocity, transform) in query.iter_mut() {
+            velocity.direction = (player.translation - transform.translation).normalize_or_zero();
+        }
+    }
+}
+
+fn attract_things(
+    attractors: Query<(&Transform, &Collider, &Attraction)>,
+    mut attractables: Query<(&mut Velocity, &Transform, &Collider), With<Attractable>>,
+) {
+    for (mut attractable_velocity, attractable_transform, attractable_collider) in
+        attractables.iter_mut()
+    {
+        for (attractor_transform, attractor_collider, attractor_attraction) in attractors.iter() {
+            let attractor_center = attractor_collider.center(attractor_transform.translation);
+            let reach = Collider::circle(attractor_attraction.radius);
+            if reach.overlaps(
+                attractor_center.extend(0.0),
+                attractable_collider,
+                attractable_transform.translation,
+            ) {
+                attractable_velocity.direction = (attractor_center
+                    - attractable_collider.center(attractable_transform.translation))
+                .normalize_or_zero()
+                .extend(0.0);
+                attractable_velocity.speed = attractor_attraction.force;
+            }
+        }
+    }
+}
+
+fn move_things(
+    clock: Res<SimulationClock>,
+    mut query: Query<(
+        &mut Transform,
+        &Velocity,
+        Option<&StatusEffects>,
+        Option<&Impulse>,
+        Option<&Blocked>,
+    )>,
+) {
+    for (mut transform, velocity, effects, impulse, blocked) in query.iter_mut() {
+        let speed = velocity.speed * effects.map_or(1.0, StatusEffects::speed_multiplier);
+        let mut movement = speed * velocity.direction;
+        if let Some(blocked) = blocked {
+            movement = blocked.slide(movement.truncate()).extend(movement.z);
+        }
+        if let Some(impulse) = impulse {
+            movement += impulse.velocity;
+        }
+        transform.translation += movement * clock.delta_seconds();
+    }
+}
+
+/// How much of each overlap is undone per pass. Less than 1 so that bodies
+/// packed on several sides ease apart instead of jittering.
+const SEPARATION_STIFFNESS: f32 = 0.5;
+/// Passes over every contact per tick. Pushing one pair apart can push either
+/// body into a third, so a dense crowd needs several to settle rather than
+/// being squeezed back together by everything chasing into it.
+const SEPARATION_ITERATIONS: usize = 4;
+
+/// How close two bodies have to be at the start of a tick to be relaxed
+/// against each other, since they can be pushed together during the passes.
+const SEPARATION_MARGIN: f32 = 1.0;
+
+/// Working space for `resolve_overlaps`, kept between ticks to reuse the
+/// allocations.
+#[derive(Default)]
+struct OverlapScratch {
+    bodies: Vec<(Entity, Vec3, Collider, f32)>,
+    indices: HashMap<Entity, usize>,
+    pairs: Vec<(usize, usize)>,
+    /// The contact normals found on the first pass, by body.
+    normals: Vec<(usize, Vec2)>,
+}
+
+fn resolve_overlaps(
+    spatial_hash: Res<SpatialHash>,
+    mut bodies: Query<
+        (
+            Entity,
+            &mut Transform,
+            &Collider,
+            &CollisionLayers,
+            Option<&Mass>,
+            Option<&mut Blocked>,
+        ),
+        (With<PreventOverlap>, With<Solid>),
+    >,
+    mut scratch: Local<OverlapScratch>,
+) {
+    let OverlapScratch {
+        bodies: positions,
+        indices,
+        pairs,
+        normals,
+    } = &mut *scratch;
+    positions.clear();
+    indices.clear();
+    pairs.clear();
+    normals.clear();
+
+    // Only solid bodies push each other, so only their layers need looking at.
+    let mut solid_layers = CollisionLayers::NONE;
+    for (body, transform, collider, layers, mass, _) in bodies.iter() {
+        solid_layers |= layers.memberships;
+        indices.insert(body, positions.len());
+        positions.push((
+            body,
+            transform.translation,
+            *collider,
+            mass.map_or(1.0, |mass| mass.0),
+        ));
+    }
+
+    // Find the pairs that might touch once, then relax them several times.
+    for (i, (_, translation, collider, _)) in positions.iter().enumerate() {
+        let center = collider.center(*translation);
+        let half_extents = collider.half_extents();
+        spatial_hash.query(center, half_extents * 2.0, solid_layers, |other| {
+            let j = match indices.get(&other) {
+                // Each pair is seen from both sides; only resolve it once.
+                Some(&j) if j > i => j,
+                _ => return,
+            };
+            // Sharing a cell isn't enough to be worth relaxing; the boxes
+            // have to be close to touching.
+            let (_, other_translation, other_collider, _) = &positions[j];
+            let gap = (center - other_collider.center(*other_translation)).abs()
+                - half_extents
+                - other_collider.half_extents();
+            if gap.max_element() < SEPARATION_MARGIN {
+                pairs.push((i, j));
+            }
+        });
+    }
+    for iteration in 0..SEPARATION_ITERATIONS {
+        for &(i, j) in pairs.iter() {
+            let (_, translation, collider, mass) = positions[i];
+            let (_, other_translation, other_collider, other_mass) = positions[j];
+            let contact = match collider.contact(translation, &other_collider, other_translation) {
+                Some(contact) => contact,
+                None => continue,
+            };
+            let normal = if contact.normal == Vec2::ZERO {
+                Vec2::X
+            } else {
+                contact.normal
+            };
+            if iteration == 0 {
+                normals.push((i, normal));
+                normals.push((j, -normal));
+            }
+            let (share, other_share) = push_shares(mass, other_mass);
+            let push = (normal * contact.depth * SEPARATION_STIFFNESS).extend(0.0);
+            positions[i].1 += push * share;
+            positions[j].1 -= push * other_share;
+        }
+    }
+
+    for &(body, translation, ..) in positions.iter() {
+        if let Ok((_, mut transform, _, _, _, blocked)) = bodies.get_mut(body) {
+            // Leave bodies that weren't pushed unchanged for change detection.
+            if transform.translation != translation {
+                transform.translation = translation;
+            }
+            if let Some(mut blocked) = blocked {
+                blocked.normals.clear();
+            }
+        }
+    }
+    for &(i, normal) in normals.iter() {
+        if let Ok((.., Some(mut blocked))) = bodies.get_mut(positions[i].0) {
+            blocked.normals.push(normal);
+        }
+    }
+}
+
+/// How much of a separation each of two bodies takes on, by their masses.
+fn push_shares(mass: f32, other_mass: f32) -> (f32, f32) {
+    match (mass.is_infinite(), other_mass.is_infinite()) {
+        (true, true) => (0.5, 0.5),
+        (true, false) => (0.0, 1.0),
+        (false, true) => (1.0, 0.0),
+        (false, false) => {
+            let total = mass + other_mass;
+            // Only reachable with bad masses, which mustn't turn into NaNs.
+            if total > 0.0 {
+                (other_mass / total, mass / total)
+            } else {
+                (0.5, 0.5)
+            }
+        }
+    }
+}
+
+fn check_collisions(
+    mut events: EventWriter<CollisionEvent>,
+    spatial_hash: Res<SpatialHash>,
+    mut collider: Query<
+        (
+            Entity,
+            &Transform,
+            &Collider,
+            &CollisionLayers,
+            Option<&mut Sweep>,
+            Option<&Punchthrough>,
+        ),
+        Changed<Transform>,
+    >,
+    obstacles: Query<(&Transform, &Collider, &CollisionLayers)>,
+) {
+    for (collider, collider_transform, collider_shape, collider_layers, sweep, punchthrough) in
+        collider.iter_mut()
+    {
+        if collider_layers.filters == CollisionLayers::NONE {
+            continue;
+        }
+        let end = collider_transform.translation;
+        if let Some(mut sweep) = sweep {
+            let start_center = collider_shape.center(sweep.start);
+            let end_center = collider_shape.center(end);
+            let mut hits = Vec::new();
+            let mut touching = Vec::new();
+            spatial_hash.query(
+                (start_center + end_center) / 2.0,
+                (end_center - start_center).abs() + collider_shape.half_extents() * 2.0,
+                collider_layers.filters,
+                |obstacle| {
+                    if obstacle == collider {
+                        return;
+                    }
+                    let (obstacle_transform, obstacle_shape, obstacle_layers) =
+                        match obstacles.get(obstacle) {
+                            Ok(obstacle) => obstacle,
+                            Err(_) => return,
+                        };
+                    if !collider_layers.interacts_with(obstacle_layers) {
+                        return;
+                    }
+                    let obstacle_translation = obstacle_transform.translation;
+                    if collider_shape.overlaps(end, obstacle_shape, obstacle_translation) {
+                        touching.push(obstacle);
+                    }
+                    if let Some(t) =
+                        collider_shape.sweep(sweep.start, end, obstacle_shape, obstacle_translation)
+                    {
+                        if t > 0.0 || !sweep.touching.contains(&obstacle) {
+                            hits.push((t, obstacle));
+                        }
+                    }
+                },
+            );
+            sweep.touching = touching;
+
+            // Report hits in the order they happened along the path, and only
+            // as many as the collider can pass through.
+            hits.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Tried to compare a NaN"));
+            let max_hits = punchthrough.map_or(usize::MAX, |punchthrough| {
+                punchthrough.amount.try_into().unwrap_or(usize::MAX)
+            });
+            for (_, obstacle) in hits.into_iter().take(max_hits) {
+                events.send(CollisionEvent { collider, obstacle });
+            }
+            continue;
+        }
+
+        spatial_hash.query(
+            collider_shape.center(end),
+            collider_shape.half_extents() * 2.0,
+            collider_layers.filters,
+            |obstacle| {
+                if let Ok((obstacle_transform, obstacle_shape, obstacle_layers)) =
+                    obstacles.get(obstacle)
+                {
+                    if obstacle != collider
+                        && collider_layers.interacts_with(obstacle_layers)
+                        && collider_shape.overlaps(
+                            end,
+                            obstacle_shape,
+                            obstacle_transform.translation,
+                        )
+                    {
+                        events.send(CollisionEvent { collider, obstacle });
+                    }
+                }
+            },
+        );
+    }
+}
+
+fn collision_damage(
+    clock: Res<SimulationClock>,
+    mut rng: ResMut<GameRng>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut damage_requests: EventWriter<DamageRequest>,
+    damagers: Query<(
+        Entity,
+        &Damage,
+        &Owner,
+        Option<&FiredFrom>,
+        Option<&AppliesEffects>,
+        Option<(&Knockback, &Velocity)>,
+    )>,
+    mut damagees: Query<(
+        Entity,
+        &Name,
+        &mut InvincibilityWindow,
+        Option<&mut StatusEffects>,
+        Option<(&mut Impulse, Option<&KnockbackResistance>)>,
+    )>,
+) {
+    for event in collision_events.iter() {
+        if let Ok((damage_ent, damage, owner, weapon, applies, knockback)) =
+            damagers.get(event.collider)
+        {
+            if let Ok((entity, _name, mut invinc_window, effects, impulse)) =
+                damagees.get_mut(event.obstacle)
+            {
+                if owner.0 == Some(entity) {
+                    continue;
+                }
+                match invinc_window.damage_sources.get_mut(&damage_ent) {
+                    Some(timer) => {
+                        timer.tick(clock.delta());
+                        if !timer.finished() {
+                            continue;
+                        } else {
+                            invinc_window.damage_sources.remove(&damage_ent);
+                        }
+                    }
+                    None => {
+                        invinc_window.damage_sources.insert(
+                            damage_ent,
+                            Timer::new(std::time::Duration::from_millis(500), false),
+                        );
+                    }
+                }
+                let (amount, crit) = damage.roll(&mut rng);
+                damage_requests.send(DamageRequest {
+                    source: Some(damage_ent),
+                    target: entity,
+                    owner: owner.0,
+                    weapon: weapon.map(|weapon| weapon.0.clone()),
+                    amount,
+                    kind: damage.kind,
+                    crit,
+                    lethal: true,
+                });
+                if let (Some(applies), Some(mut effects)) = (applies, effects) {
+                    for effect in &applies.0 {
+                        effects.apply(effect, weapon.map(|weapon| weapon.0.as_str()));
+                    }
+                }
+                if let (Some((knockback, velocity)), Some((mut impulse, resistance))) =
+                    (knockback, impulse)
+                {
+                    impulse.push(velocity.direction, knockback.force, resistance);
+                }
+            }
+        }
+    }
+}
+
+fn bullet_collision(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut bullets: Query<(Entity, &mut Punchthrough), With<Bullet>>,
+) {
+    for event in collision_events.iter() {
+        if let Ok((entity, mut punchthrough)) = bullets.get_mut(event.collider) {
+            if punchthrough.amount == 0 {
+                continue;
+            }
+            punchthrough.amount -= 1;
+            if punchthrough.amount == 0 {
+                death_events.send(DeathEvent { entity });
+            }
+        }
+    }
+}
+
+fn exp_pickup_collision(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut player: Query<&mut Experience, (With<Player>, Without<Pickup>)>,
+    pickups: Query<(Entity, &Experience), With<Pickup>>,
+) {
+    for event in collision_events.iter() {
+        if let Ok((pickup, pickup_exp)) = pickups.get(event.obstacle) {
+            if let Ok(mut player_exp) = player.get_mut(event.collider) {
+                player_exp.amount += pickup_exp.amount;
+                death_events.send(DeathEvent { entity: pickup });
+            }
+        }
+    }
+}
+
+fn handle_exp_drop_on_death(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    query: Query<(&DropExpOnDeath, &Transform)>,
+) {
+    let mut handled: HashSet<Entity> = HashSet::new();
+    for event in death_events.iter() {
+        if let Ok((drop_exp, transform)) = query.get(event.entity) {
+            if !handled.contains(&event.entity) {
+                spawn_exp_drop(&mut commands, transform.translation, drop_exp.amount);
+                handled.insert(event.entity);
+            }
+        }
+    }
+}
+
+fn handle_health_change(
+    mut texts: Query<&mut Text, With<HudText>>,
+    players: Query<&Health, (With<Player>, Changed<Health>)>,
+) {
+    let mut text = texts.single_mut();
+
+    for health in players.iter() {
+        text.sections[1].value = format!("{}", health.current);
+    }
+}
+
+fn handle_experience_change(
+    mut texts: Query<&mut Text, With<HudText>>,
+    players: Query<&Experience, (With<Player>, Changed<Experience>)>,
+) {
+    let mut text = texts.single_mut();
+
+    for experience in players.iter() {
+        text.sections[3].value = format!("{}", experience.amount);
+    }
+}
+
+fn handle_seed_change(mut texts: Query<&mut Text, With<HudText>>, rng: Res<GameRng>) {
+    if rng.is_changed() {
+        texts.single_mut().sections[4].value = format!("  Seed: {}", rng.seed());
+    }
+}
+
+fn handle_death(mut death_events: EventReader<DeathEvent>, mut commands: Commands) {
+    let mut handled: HashSet<Entity> = HashSet::new();
+    for event in death_events.iter() {
+        if !handled.contains(&event.entity) {
+            commands.entity(event.entity).despawn_recursive();
+            handled.insert(event.entity);
+        }
+    }
+}
+
+fn handle_player_death(
+    mut death_events: EventReader<DeathEvent>,
+    mut texts: Query<&mut Text, With<HudText>>,
+    query: Query<Entity, With<Player>>,
+) {
+    let mut text = texts.single_mut();
+    for event in death_events.iter() {
+        if query.get(event.entity).is_ok() {
+            text.sections[1].value = format!("{}", 0);
+        }
+    }
+}
+
+fn cleanup_invincibility_windows(
+    mut death_events: EventReader<DeathEvent>,
+    mut invincibility_windows: Query<&mut InvincibilityWindow>,
+) {
+    for event in death_events.iter() {
+        for mut window in invincibility_windows.iter_mut() {
+            window.damage_sources.remove(&event.entity);
+        }
+    }
+}
+
+fn check_lifetimes(
+    clock: Res<SimulationClock>,
+    mut events: EventWriter<DeathEvent>,
+    mut lifetimes: Query<(Entity, &mut Lifetime)>,
+) {
+    for (entity, mut lifetime) in lifetimes.iter_mut() {
+        lifetime.timer.tick(clock.delta());
+        if lifetime.timer.finished() {
+            events.send(DeathEvent { entity });
+        }
+    }
+}
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WindowDescriptor {
+            title: "Platformer!".to_string(),
+            width: 640.0,
+            height: 400.0,
+            ..Default::default()
+        })
+        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
+        .add_state(GameState::MainMenu)
+        .add_plugin(SimulationPlugin)
+        .add_startup_system(setup_health_display)
+        .add_system(handle_health_change)
+        .add_system(handle_experience_change)
+        .add_system(handle_seed_change)
+        .add_simulation_system_set(
+            SimulationStage::Cleanup,
+            SystemSet::new().with_system(handle_player_death),
+        )
+        .add_system(bevy::input::system::exit_on_esc_system);
+        simulation::add_transform_interpolation(app);
+        camera::add_camera(app);
+        damage_numbers::add_damage_numbers(app);
+        input::add_input_actions(app);
+        state::add_state_screens(app);
+        upgrade::add_level_up_screen(app);
+    }
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<GameRng>() {
+            app.insert_resource(GameRng::from_entropy());
+        }
+        if !app.world.contains_resource::<State<GameState>>() {
+            app.add_state(GameState::Playing);
+        }
+        simulation::add_simulation_schedule(app);
+        if !app.world.contains_resource::<EnemyArchetypes>() {
+            app.insert_resource(EnemyArchetypes::load());
+        }
+        if !app.world.contains_resource::<WaveTimeline>() {
+            app.insert_resource(WaveTimeline::load());
+        }
+        if !app.world.contains_resource::<WeaponDefinitions>() {
+            app.insert_resource(WeaponDefinitions::load());
+        }
+        app.init_resource::<WaveDirector>()
+            .init_resource::<CameraView>()
+            .init_resource::<CameraFollow>()
+            .init_resource::<ArenaBounds>()
+            .init_resource::<ActionState>()
+            .init_resource::<RunStats>()
+            .init_resource::<SpatialHash>()
+            .add_simulation_event::<CollisionEvent>()
+            .add_simulation_event::<DeathEvent>()
+            .add_simulation_event::<DamageRequest>()
+            .add_simulation_event::<DamageEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(state::reset_run)
+                    .with_system(setup),
+            )
+            .add_simulation_system_set(
+                SimulationStage::Tick,
+                SystemSet::new()
+                    .with_system(enemy_ai)
+                    .with_system(check_lifetimes)
+                    .with_system(attract_things.before(move_things))
+                    .with_system(move_things.after(enemy_ai))
+                    .with_system(collision::start_sweeps.before(move_things))
+                    .with_system(
+                        knockback::decay_impulses
+                            .after(move_things)
+                            .before(check_collisions),
+                    )
+                    .with_system(handle_input.before(move_things))
+                    .with_system(move_dashing.after(handle_input).before(move_things))
+                    .with_system(weapon::fire_weapons.before(move_things))
+                    .with_system(spatial::update_spatial_hash.after(move_things))
+                    .with_system(check_collisions.after(spatial::update_spatial_hash))
+                    // Contacts are reported from where things moved to, before
+                    // they're pushed apart again.
+                    .with_system(resolve_overlaps.after(check_collisions))
+                    .with_system(collision_damage.after(check_collisions))
+                    .with_system(bullet_collision.after(check_collisions))
+                    .with_system(exp_pickup_collision.after(check_collisions))
+                    .with_system(status::tick_status_effects.after(collision_damage))
+                    .with_system(
+                        damage::apply_damage
+                            .after(collision_damage)
+                            .after(status::tick_status_effects),
+                    )
+                    .with_system(state::track_experience.after(exp_pickup_collision))
+                    .with_system(state::track_weapon_stats.after(damage::apply_damage))
+                    // These draw from `GameRng`, so their order has to be fixed
+                    // for a seed to reproduce a run.
+                    .with_system(
+                        upgrade::check_level_up
+                            .after(weapon::fire_weapons)
+                            .after(exp_pickup_collision)
+                            .after(damage::apply_damage),
+                    )
+                    .with_system(camera::follow_player.after(move_things))
+                    .with_system(
+                        arena::bounce_off_arena_edges
+                            .after(camera::follow_player)
+                            .before(spatial::update_spatial_hash),
+                    )
+                    .with_system(
+                        director::run_wave_director
+                            .after(upgrade::check_level_up)
+                            .after(camera::follow_player),
+                    ),
+            )
+            .add_simulation_system_set(
+                SimulationStage::Cleanup,
+                SystemSet::new()
+                    .with_system(handle_death)
+                    .with_system(handle_exp_drop_on_death.before(handle_death))
+                    .with_system(cleanup_invincibility_windows.after(handle_death))
+                    .with_system(state::count_kills.before(handle_death))
+                    .with_system(state::end_run_on_player_death)
+                    .with_system(camera::despawn_distant_enemies),
+            );
+    }
+}
+
+/// A snapshot of the state of a run, for reporting on headless simulations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub seed: u64,
+    pub frames: u64,
+    pub survived: std::time::Duration,
+    pub player_health: Option<u32>,
+    pub player_experience: u32,
+    pub kills: u32,
+    pub enemies: usize,
+    pub weapons: Vec<WeaponTally>,
+}
+
+/// Runs `SimulationPlugin` on top of `MinimalPlugins`, one frame at a time.
+///
+/// Nothing is rendered, so this can be driven from tests, CI or balancing
+/// scripts.
+pub struct HeadlessGame {
+    app: App,
+    frames: u64,
+}
+
+impl HeadlessGame {
+    pub fn new() -> Self {
+        Self::with_rng(GameRng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(GameRng::new(seed))
+    }
+
+    /// A run where grunts that can't hurt the player keep closing in on it
+    /// from every side until `count` of them are alive, for measuring how
+    /// crowds behave and perform.
+    pub fn crowd(count: usize) -> Self {
+        let mut game = Self::with_seed(1);
+        let world = game.world_mut();
+        let mut archetypes = world.resource_mut::<EnemyArchetypes>();
+        let mut dummy = archetypes.get("grunt").expect("No grunt archetype").clone();
+        dummy.id = "dummy".to_string();
+        dummy.damage = 0;
+        archetypes.insert(dummy);
+        world.insert_resource(WaveTimeline {
+            max_enemies: count,
+            waves: vec![Wave {
+                start: 0.0,
+                end: f32::MAX,
+                archetype: "dummy".to_string(),
+                interval: 0.05,
+                count: 100,
+                health: 1000.0,
+                speed: 1.0,
+                shape: SpawnShape::Circle,
+            }],
+        });
+        game
+    }
+
+    fn with_rng(rng: GameRng) -> Self {
+        let mut app = App::new();
+        app.insert_resource(rng)
+            .insert_resource(SimulationClock::default().lockstep())
+            .insert_resource(AutoPickUpgrades)
+            .add_plugins(MinimalPlugins)
+            .add_plugin(bevy::input::InputPlugin)
+            .add_plugin(SimulationPlugin);
+        HeadlessGame { app, frames: 0 }
+    }
+
+    /// Advances the game by `frames` updates of the whole schedule. Each frame
+    /// is exactly one simulation tick, however long it takes to run.
+    pub fn step(&mut self, frames: u64) {
+        for _ in 0..frames {
+            self.app.update();
+            self.frames += 1;
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    pub fn summary(&mut self) -> RunSummary {
+        let world = &mut self.app.world;
+        let player = world
+            .query_filtered::<(&Health, &Experience), With<Player>>()
+            .iter(world)
+            .next()
+            .map(|(health, experience)| (health.current, experience.amount));
+        let enemies = world.query::<&Enemy>().iter(world).len();
+        RunSummary {
+            seed: world.resource::<GameRng>().seed(),
+            frames: self.frames,
+            survived: world.resource::<SimulationClock>().elapsed(),
+            player_health: player.map(|(health, _)| health),
+            player_experience: player.map_or(0, |(_, experience)| experience),
+            kills: world.resource::<RunStats>().kills,
+            enemies,
+            weapons: world.resource::<RunStats>().weapons.clone(),
+        }
+    }
+}
+
+impl Default for HeadlessGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+
+    /// Deepest overlap between any two enemies.
+    fn max_overlap(game: &mut HeadlessGame) -> f32 {
+        let world = game.world_mut();
+        let bodies: Vec<(Vec3, Collider)> = world
+            .query_filtered::<(&Transform, &Collider), With<Enemy>>()
+            .iter(world)
+            .map(|(transform, collider)| (transform.translation, *collider))
+            .collect();
+        let mut deepest: f32 = 0.0;
+        for (i, (translation, collider)) in bodies.iter().enumerate() {
+            for (other_translation, other_collider) in &bodies[i + 1..] {
+                if let Some(contact) =
+                    collider.contact(*translation, other_collider, *other_translation)
+                {
+                    deepest = deepest.max(contact.depth);
+                }
+            }
+        }
+        deepest
+    }
+
+    #[test]
+    fn push_shares_never_give_nan() {
+        assert_eq!(push_shares(1.0, 3.0), (0.75, 0.25));
+        assert_eq!(push_shares(f32::INFINITY, 1.0), (0.0, 1.0));
+        assert_eq!(push_shares(f32::INFINITY, f32::INFINITY), (0.5, 0.5));
+        assert_eq!(push_shares(0.0, 0.0), (0.5, 0.5));
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let mut first = HeadlessGame::with_seed(5);
+        let mut second = HeadlessGame::with_seed(5);
+        first.step(1200);
+        second.step(1200);
+        let summary = first.summary();
+        assert!(summary.kills > 0, "Nothing happened in the run");
+        assert_eq!(summary, second.summary());
+    }
+
+    /// The obstacles a bullet moving from `start` to `end` reports hitting, in
+    /// the order they're sent, against static boxes at each of `obstacles`.
+    fn sweep_hits(
+        start: Vec3,
+        end: Vec3,
+        obstacles: &[f32],
+        punchthrough: Option<u32>,
+    ) -> Vec<f32> {
+        let mut world = World::new();
+        world.insert_resource(SpatialHash::default());
+        world.init_resource::<Events<CollisionEvent>>();
+        for &x in obstacles {
+            world
+                .spawn()
+                .insert(Transform::from_xyz(x, 0.0, 0.0))
+                .insert(Collider::aabb(Vec2::splat(10.0)))
+                .insert(CollisionLayers::new(
+                    CollisionLayers::ENEMY,
+                    CollisionLayers::NONE,
+                ));
+        }
+        let mut bullet = world.spawn();
+        bullet
+            .insert(Transform::from_translation(end))
+            .insert(Collider::circle(2.0))
+            .insert(CollisionLayers::new(
+                CollisionLayers::PLAYER_PROJECTILE,
+                CollisionLayers::ENEMY,
+            ))
+            .insert(Sweep {
+                start,
+                touching: Vec::new(),
+            });
+        if let Some(amount) = punchthrough {
+            bullet.insert(Punchthrough { amount });
+        }
+
+        SystemStage::single_threaded()
+            .with_system(spatial::update_spatial_hash)
+            .with_system(check_collisions.after(spatial::update_spatial_hash))
+            .run(&mut world);
+
+        let events = world.resource::<Events<CollisionEvent>>();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|event| {
+                world
+                    .get::<Transform>(event.obstacle)
+                    .unwrap()
+                    .translation
+                    .x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sweep_reports_hits_along_the_path() {
+        let start = Vec3::new(100.0, 0.0, 0.0);
+        let end = Vec3::new(-100.0, 0.0, 0.0);
+        let obstacles = [-60.0, 60.0, 0.0, 200.0];
+        assert_eq!(sweep_hits(start, end, &obstacles, None), [60.0, 0.0, -60.0]);
+        assert_eq!(sweep_hits(end, start, &obstacles, None), [-60.0, 0.0, 60.0]);
+    }
+
+    #[test]
+    fn sweep_stops_at_punchthrough() {
+        let start = Vec3::new(100.0, 0.0, 0.0);
+        let end = Vec3::new(-100.0, 0.0, 0.0);
+        let obstacles = [-60.0, 60.0, 0.0];
+        assert_eq!(sweep_hits(start, end, &obstacles, Some(2)), [60.0, 0.0]);
+        assert_eq!(sweep_hits(start, end, &obstacles, Some(1)), [60.0]);
+    }
+
+    #[test]
+    fn crowd_does_not_stack() {
+        let mut game = HeadlessGame::crowd(400);
+        for _ in 0..4 {
+            game.step(300);
+            let deepest = max_overlap(&mut game);
+            assert!(deepest < 2.0, "enemies overlap by {} px", deepest);
+        }
+        let world = game.world_mut();
+        assert_eq!(world.query::<&Enemy>().iter(world).count(), 400);
+    }
+}
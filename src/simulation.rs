@@ -0,0 +1,339 @@
+use bevy::{
+    ecs::{event::Events, schedule::ShouldRun, system::Resource},
+    prelude::*,
+    transform::TransformSystem,
+};
+use std::time::Duration;
+
+use crate::{GameState, Velocity};
+
+/// The stages of the gameplay schedule. The whole schedule runs once per
+/// simulation tick, which may be zero, one or several times per frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub enum SimulationStage {
+    PreTick,
+    Tick,
+    Cleanup,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+struct Simulation;
+
+/// The clock gameplay runs on. Every gameplay system should take its delta
+/// from here rather than from `Time`, so that results don't depend on the
+/// frame rate.
+pub struct SimulationClock {
+    step: Duration,
+    accumulator: Duration,
+    elapsed: Duration,
+    ticks: u64,
+    max_ticks_per_frame: u32,
+    ticks_this_frame: u32,
+    lockstep: bool,
+    interrupted: bool,
+    paused: bool,
+    pending_steps: u32,
+}
+
+impl SimulationClock {
+    pub fn new(hz: u32) -> Self {
+        SimulationClock {
+            step: Duration::from_secs_f64(1.0 / hz as f64),
+            accumulator: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            ticks: 0,
+            max_ticks_per_frame: 5,
+            ticks_this_frame: 0,
+            lockstep: false,
+            interrupted: false,
+            paused: false,
+            pending_steps: 0,
+        }
+    }
+
+    /// Runs exactly one tick per frame, ignoring wall-clock time.
+    pub fn lockstep(mut self) -> Self {
+        self.lockstep = true;
+        self
+    }
+
+    pub fn delta(&self) -> Duration {
+        self.step
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    /// Simulated time since the start of the run.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// How far the frame is between the last tick and the next one, from 0 to 1.
+    pub fn overstep(&self) -> f32 {
+        if self.lockstep || self.paused {
+            1.0
+        } else {
+            self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+        }
+    }
+
+    /// Starts the clock over for a new run.
+    pub fn reset(&mut self) {
+        self.accumulator = Duration::ZERO;
+        self.elapsed = Duration::ZERO;
+        self.ticks = 0;
+    }
+
+    /// Stops running ticks for the rest of this frame, for when the state is
+    /// about to change away from `Playing`.
+    pub fn interrupt(&mut self) {
+        self.interrupted = true;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Runs exactly one tick on the next frame, even though the clock is
+    /// paused.
+    pub fn request_step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    /// Adds the time that passed during a frame. While paused, no time is
+    /// added at all, so that nothing has to be caught up on after resuming.
+    fn accumulate(&mut self, frame_delta: Duration, running: bool) {
+        self.ticks_this_frame = 0;
+        self.interrupted = false;
+        self.paused = !running;
+        if !running {
+            return;
+        }
+        self.pending_steps = 0;
+        if self.lockstep {
+            self.accumulator = self.step;
+        } else {
+            // Drop time we can't catch up on rather than spiralling further behind.
+            let max_backlog = self.step * self.max_ticks_per_frame;
+            self.accumulator = (self.accumulator + frame_delta).min(max_backlog);
+        }
+    }
+
+    fn consume_tick(&mut self) -> bool {
+        if self.interrupted {
+            return false;
+        }
+        if self.paused {
+            if self.pending_steps == 0 || self.ticks_this_frame > 0 {
+                return false;
+            }
+            self.pending_steps -= 1;
+        } else if self.accumulator < self.step || self.ticks_this_frame >= self.max_ticks_per_frame
+        {
+            return false;
+        } else {
+            self.accumulator -= self.step;
+        }
+        self.elapsed += self.step;
+        self.ticks += 1;
+        self.ticks_this_frame += 1;
+        true
+    }
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self::new(60)
+    }
+}
+
+fn accumulate_simulation_time(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    clock.accumulate(time.delta(), *state.current() == GameState::Playing);
+}
+
+fn run_simulation_tick(mut clock: ResMut<SimulationClock>) -> ShouldRun {
+    if clock.consume_tick() {
+        ShouldRun::YesAndCheckAgain
+    } else {
+        ShouldRun::No
+    }
+}
+
+/// Adds systems to the gameplay schedule rather than to the per-frame one.
+pub trait SimulationApp {
+    fn add_simulation_system_set(
+        &mut self,
+        stage: SimulationStage,
+        system_set: SystemSet,
+    ) -> &mut Self;
+
+    /// Events that are sent and read within the gameplay schedule have to be
+    /// cleared per tick rather than per frame, or they would be dropped or
+    /// seen twice whenever a frame runs a different number of ticks.
+    fn add_simulation_event<T: Resource>(&mut self) -> &mut Self;
+}
+
+impl SimulationApp for App {
+    fn add_simulation_system_set(
+        &mut self,
+        stage: SimulationStage,
+        system_set: SystemSet,
+    ) -> &mut Self {
+        self.stage(Simulation, |schedule: &mut Schedule| {
+            schedule.add_system_set_to_stage(stage, system_set)
+        })
+    }
+
+    fn add_simulation_event<T: Resource>(&mut self) -> &mut Self {
+        self.init_resource::<Events<T>>().add_simulation_system_set(
+            SimulationStage::PreTick,
+            SystemSet::new().with_system(Events::<T>::update_system),
+        )
+    }
+}
+
+pub(crate) fn add_simulation_schedule(app: &mut App) {
+    if !app.world.contains_resource::<SimulationClock>() {
+        app.init_resource::<SimulationClock>();
+    }
+    let schedule = Schedule::default()
+        .with_run_criteria(IntoSystem::into_system(run_simulation_tick))
+        .with_stage(SimulationStage::PreTick, SystemStage::parallel())
+        .with_stage(SimulationStage::Tick, SystemStage::parallel())
+        .with_stage(SimulationStage::Cleanup, SystemStage::single_threaded());
+    app.add_stage_after(CoreStage::Update, Simulation, schedule)
+        .add_system_to_stage(CoreStage::PreUpdate, accumulate_simulation_time);
+}
+
+/// Where an entity was at the start of the latest tick, so that it can be
+/// drawn part way between ticks. Anything moved other than by its velocity
+/// should move this along with it, or it streaks there over one tick.
+#[derive(Component)]
+pub(crate) struct Interpolated {
+    pub(crate) previous: Vec3,
+}
+
+fn record_previous_translations(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, Option<&mut Interpolated>), With<Velocity>>,
+) {
+    for (entity, transform, interpolated) in query.iter_mut() {
+        match interpolated {
+            Some(mut interpolated) => interpolated.previous = transform.translation,
+            None => {
+                commands.entity(entity).insert(Interpolated {
+                    previous: transform.translation,
+                });
+            }
+        }
+    }
+}
+
+fn interpolate_transforms(
+    clock: Res<SimulationClock>,
+    mut query: Query<(&Transform, &Interpolated, &mut GlobalTransform), Without<Parent>>,
+) {
+    let alpha = clock.overstep();
+    for (transform, interpolated, mut global_transform) in query.iter_mut() {
+        global_transform.translation = interpolated.previous.lerp(transform.translation, alpha);
+    }
+}
+
+/// Smooths the movement of anything with a `Velocity` between simulation
+/// ticks. Only the drawn `GlobalTransform` is touched; gameplay keeps reading
+/// `Transform`.
+pub(crate) fn add_transform_interpolation(app: &mut App) {
+    app.add_simulation_system_set(
+        SimulationStage::PreTick,
+        SystemSet::new().with_system(record_previous_translations),
+    )
+    .add_system_to_stage(
+        CoreStage::PostUpdate,
+        interpolate_transforms.after(TransformSystem::TransformPropagate),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a frame `frame_delta` long, returning how many ticks it ran.
+    fn frame(clock: &mut SimulationClock, frame_delta: Duration, running: bool) -> u32 {
+        clock.accumulate(frame_delta, running);
+        let mut ticks = 0;
+        while clock.consume_tick() {
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    fn ticks_follow_frame_time() {
+        let mut clock = SimulationClock::new(60);
+        let step = clock.delta();
+        assert_eq!(frame(&mut clock, step, true), 1);
+        assert_eq!(frame(&mut clock, step / 2, true), 0);
+        assert_eq!(frame(&mut clock, step - step / 2, true), 1);
+        assert_eq!(frame(&mut clock, step * 3, true), 3);
+        assert_eq!(clock.ticks(), 5);
+        assert_eq!(clock.elapsed(), step * 5);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut clock = SimulationClock::new(60);
+        let step = clock.delta();
+        assert_eq!(frame(&mut clock, Duration::from_secs(1), true), 5);
+        // The rest of the second is dropped rather than caught up on later.
+        assert_eq!(frame(&mut clock, Duration::ZERO, true), 0);
+        assert_eq!(clock.elapsed(), step * 5);
+    }
+
+    #[test]
+    fn paused_clock_only_runs_requested_steps() {
+        let mut clock = SimulationClock::new(60);
+        let step = clock.delta();
+        assert_eq!(frame(&mut clock, Duration::from_secs(1), false), 0);
+        assert!(clock.is_paused());
+
+        clock.request_step();
+        clock.request_step();
+        // One step per frame, however long the frames are.
+        assert_eq!(frame(&mut clock, Duration::from_secs(1), false), 1);
+        assert_eq!(frame(&mut clock, Duration::ZERO, false), 1);
+        assert_eq!(frame(&mut clock, Duration::from_secs(1), false), 0);
+        assert_eq!(clock.elapsed(), step * 2);
+
+        // Steps asked for while paused don't carry over into play, and no
+        // time passed while paused.
+        clock.request_step();
+        assert_eq!(frame(&mut clock, Duration::ZERO, true), 0);
+        assert!(!clock.is_paused());
+        clock.request_step();
+        assert_eq!(frame(&mut clock, Duration::ZERO, true), 0);
+    }
+
+    #[test]
+    fn interrupt_stops_the_frame() {
+        let mut clock = SimulationClock::new(60);
+        let step = clock.delta();
+        clock.accumulate(step * 3, true);
+        assert!(clock.consume_tick());
+        clock.interrupt();
+        assert!(!clock.consume_tick());
+        // The next frame carries on with what was left.
+        assert_eq!(frame(&mut clock, Duration::ZERO, true), 2);
+    }
+}
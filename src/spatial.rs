@@ -0,0 +1,134 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{Collider, CollisionLayers};
+
+/// One entity's listing in a cell.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    entity: Entity,
+    memberships: u32,
+    /// The lowest cell the entity is listed in, so that a query spanning
+    /// several of its cells reports it only once.
+    first_cell: IVec2,
+}
+
+/// A uniform grid over the world, used as the broadphase for collisions.
+/// Each entity is listed in every cell its bounding box touches, so lookups
+/// only have to look at the handful of cells around the area of interest.
+/// Every collision layer has a grid of its own, so that looking for one layer
+/// never has to step over crowds on another.
+pub struct SpatialHash {
+    cell_size: f32,
+    layers: Vec<HashMap<IVec2, Vec<Entry>>>,
+    /// The layers anything is listed on.
+    occupied: u32,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size,
+            layers: (0..u32::BITS).map(|_| HashMap::default()).collect(),
+            occupied: 0,
+        }
+    }
+
+    fn cell_range(&self, center: Vec2, size: Vec2) -> (IVec2, IVec2) {
+        let half = size / 2.0;
+        let min = ((center - half) / self.cell_size).floor();
+        let max = ((center + half) / self.cell_size).floor();
+        (min.as_ivec2(), max.as_ivec2())
+    }
+
+    /// Empties every cell, keeping the allocations of the ones that were used
+    /// since the last clear and dropping the rest, so that the grids don't
+    /// keep every cell a crowd has ever passed through.
+    pub fn clear(&mut self) {
+        for cells in &mut self.layers {
+            cells.retain(|_, entities| {
+                let keep = !entities.is_empty();
+                entities.clear();
+                keep
+            });
+        }
+        self.occupied = 0;
+    }
+
+    /// Lists `entity` on each of the layers in `memberships`.
+    pub fn insert(&mut self, entity: Entity, memberships: u32, center: Vec2, size: Vec2) {
+        let (min, max) = self.cell_range(center, size);
+        let entry = Entry {
+            entity,
+            memberships,
+            first_cell: min,
+        };
+        for layer in layer_indices(memberships) {
+            let cells = &mut self.layers[layer];
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    cells.entry(IVec2::new(x, y)).or_default().push(entry);
+                }
+            }
+        }
+        self.occupied |= memberships;
+    }
+
+    /// Calls `visit` once for every entity on one of `layers` that shares a
+    /// cell with the given box, in a stable order. These are candidates only;
+    /// the caller still has to do the exact overlap test.
+    pub fn query(&self, center: Vec2, size: Vec2, layers: u32, mut visit: impl FnMut(Entity)) {
+        let layers = layers & self.occupied;
+        if layers == 0 {
+            return;
+        }
+        let (min, max) = self.cell_range(center, size);
+        for layer in layer_indices(layers) {
+            let cells = &self.layers[layer];
+            // Entities on several of the layers asked for are reported from the
+            // lowest of them.
+            let lower_layers = layers & ((1u32 << layer) - 1);
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    let cell = IVec2::new(x, y);
+                    for entry in cells.get(&cell).into_iter().flatten() {
+                        if entry.memberships & lower_layers == 0
+                            && entry.first_cell.max(min) == cell
+                        {
+                            visit(entry.entity);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The index of each layer set in `layers`, lowest first.
+fn layer_indices(layers: u32) -> impl Iterator<Item = usize> {
+    (0..u32::BITS as usize).filter(move |&layer| layers & (1u32 << layer) != 0)
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(32.0)
+    }
+}
+
+/// Lists every collider on its layers, whether it moves or not.
+pub(crate) fn update_spatial_hash(
+    mut spatial_hash: ResMut<SpatialHash>,
+    query: Query<(Entity, &Transform, &Collider, &CollisionLayers)>,
+) {
+    spatial_hash.clear();
+    for (entity, transform, collider, layers) in query.iter() {
+        if layers.memberships == CollisionLayers::NONE {
+            continue;
+        }
+        spatial_hash.insert(
+            entity,
+            layers.memberships,
+            collider.center(transform.translation),
+            collider.half_extents() * 2.0,
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use bevy::{prelude::*, utils::HashSet};
+use rand::Rng;
+use std::time::Duration;
+
+use crate::{
+    input::REBINDABLE, Action, ActionState, CameraView, DamageEvent, DeathEvent, Enemy, Experience,
+    GameRng, InputBindings, Player, Rebinding, SimulationClock, UpgradeChoice, WaveDirector,
+    Weapons,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    MainMenu,
+    Playing,
+    Paused,
+    /// Waiting for the player to pick an upgrade. See `UpgradeChoice`.
+    LevelUp,
+    GameOver,
+}
+
+/// Belongs to the current run, and is despawned when the next one starts.
+#[derive(Component)]
+pub(crate) struct RunEntity;
+
+/// Tallies for the current run, shown on the game-over screen.
+#[derive(Debug, Default, Clone)]
+pub struct RunStats {
+    pub kills: u32,
+    pub experience: u32,
+    /// One tally per weapon the player has held, in the order they got them.
+    pub weapons: Vec<WeaponTally>,
+}
+
+/// What one weapon has done over the run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeaponTally {
+    pub weapon: String,
+    /// When in the run the player got the weapon.
+    pub acquired: Duration,
+    pub damage: u32,
+    pub hits: u32,
+    pub kills: u32,
+}
+
+impl WeaponTally {
+    /// Damage per second since the weapon was picked up, as of `now`.
+    pub fn dps(&self, now: Duration) -> f32 {
+        let held = now.saturating_sub(self.acquired).as_secs_f32();
+        if held > 0.0 {
+            self.damage as f32 / held
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Clears out the previous run, if there was one. The first run keeps the seed
+/// it was started with; every later one draws a fresh seed from the last, so
+/// that each run can be reproduced from the seed shown on the HUD.
+pub(crate) fn reset_run(
+    mut commands: Commands,
+    mut clock: ResMut<SimulationClock>,
+    mut rng: ResMut<GameRng>,
+    mut view: ResMut<CameraView>,
+    mut started_before: Local<bool>,
+    entities: Query<Entity, With<RunEntity>>,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.insert_resource(WaveDirector::default());
+    commands.insert_resource(RunStats::default());
+    commands.remove_resource::<UpgradeChoice>();
+    clock.reset();
+    view.jump_to(Vec2::ZERO);
+    if *started_before {
+        *rng = GameRng::new(rng.gen());
+    }
+    *started_before = true;
+}
+
+pub(crate) fn count_kills(
+    mut death_events: EventReader<DeathEvent>,
+    mut stats: ResMut<RunStats>,
+    enemies: Query<Entity, With<Enemy>>,
+) {
+    let mut handled: HashSet<Entity> = HashSet::new();
+    for event in death_events.iter() {
+        if enemies.get(event.entity).is_ok() && handled.insert(event.entity) {
+            stats.kills += 1;
+        }
+    }
+}
+
+pub(crate) fn track_experience(
+    mut stats: ResMut<RunStats>,
+    players: Query<&Experience, (With<Player>, Changed<Experience>)>,
+) {
+    for experience in players.iter() {
+        stats.experience = experience.amount;
+    }
+}
+
+/// Puts damage, hits and kills down to the weapon behind them. Damage over time
+/// adds to a weapon's damage, but only direct hits count as hits.
+pub(crate) fn track_weapon_stats(
+    clock: Res<SimulationClock>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut stats: ResMut<RunStats>,
+    players: Query<&Weapons, With<Player>>,
+) {
+    for weapons in players.iter() {
+        for weapon in weapons.iter() {
+            if !stats
+                .weapons
+                .iter()
+                .any(|tally| tally.weapon == weapon.id())
+            {
+                stats.weapons.push(WeaponTally {
+                    weapon: weapon.id().to_string(),
+                    acquired: clock.elapsed(),
+                    damage: 0,
+                    hits: 0,
+                    kills: 0,
+                });
+            }
+        }
+    }
+    for event in damage_events.iter() {
+        let weapon = match &event.weapon {
+            Some(weapon) => weapon,
+            None => continue,
+        };
+        if let Some(tally) = stats
+            .weapons
+            .iter_mut()
+            .find(|tally| &tally.weapon == weapon)
+        {
+            tally.damage += event.amount;
+            if event.source.is_some() {
+                tally.hits += 1;
+            }
+            if event.fatal {
+                tally.kills += 1;
+            }
+        }
+    }
+}
+
+/// Switches away from `Playing`. The simulation only sees the new state next
+/// frame, so this also makes sure no more ticks run in this one.
+pub(crate) fn leave_playing(
+    clock: &mut SimulationClock,
+    state: &mut State<GameState>,
+    next: GameState,
+) {
+    clock.interrupt();
+    let _ = match next {
+        // Overlays go on top of the run, so that popping them resumes it.
+        GameState::Paused | GameState::LevelUp => state.push(next),
+        // Overwrite rather than set, in case a level-up was queued this tick.
+        _ => state.overwrite_set(next),
+    };
+}
+
+pub(crate) fn end_run_on_player_death(
+    mut death_events: EventReader<DeathEvent>,
+    mut state: ResMut<State<GameState>>,
+    mut clock: ResMut<SimulationClock>,
+    players: Query<Entity, With<Player>>,
+) {
+    for event in death_events.iter() {
+        if players.get(event.entity).is_ok() {
+            leave_playing(&mut clock, &mut state, GameState::GameOver);
+            return;
+        }
+    }
+}
+
+/// A full-screen menu or overlay, despawned when its state is left.
+#[derive(Component)]
+pub(crate) struct Screen(GameState);
+
+pub(crate) fn spawn_screen(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    state: GameState,
+    lines: &[(String, f32)],
+) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(Screen(state))
+        .with_children(|parent| {
+            for (line, font_size) in lines {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        line.clone(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: *font_size,
+                            color: Color::rgb(1.0, 1.0, 1.0),
+                        },
+                        default(),
+                    ),
+                    ..default()
+                });
+            }
+        });
+}
+
+fn despawn_screens(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    screens: Query<(Entity, &Screen)>,
+) {
+    for (entity, screen) in screens.iter() {
+        if screen.0 != *state.current() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_screen(
+        &mut commands,
+        &asset_server,
+        GameState::MainMenu,
+        &[
+            ("Horde Survivor".to_string(), 60.0),
+            ("Press Enter to start".to_string(), 24.0),
+        ],
+    );
+}
+
+fn pause_screen_lines(bindings: &InputBindings, rebinding: &Rebinding) -> Vec<(String, f32)> {
+    let mut lines = vec![
+        ("Paused".to_string(), 60.0),
+        (
+            format!("Press {} to resume", bindings.describe_keys(Action::Pause)),
+            24.0,
+        ),
+    ];
+    for (i, action) in REBINDABLE.iter().enumerate() {
+        lines.push((
+            format!(
+                "{}. {}: {}",
+                i + 1,
+                action.label(),
+                bindings.describe_keys(*action)
+            ),
+            20.0,
+        ));
+    }
+    if let Some((key, other)) = rebinding.conflict {
+        lines.push((
+            format!("{:?} is already bound to {}", key, other.label()),
+            20.0,
+        ));
+    }
+    match rebinding.action {
+        Some(action) => lines.push((format!("Press a key for {}", action.label()), 24.0)),
+        None => lines.push(("Press a number to rebind".to_string(), 24.0)),
+    }
+    if cfg!(debug_assertions) {
+        lines.push(("Press . to advance one tick".to_string(), 24.0));
+    }
+    lines
+}
+
+/// Shows the pause screen, and redraws it whenever the bindings on it change.
+fn show_pause_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    screens: Query<(Entity, &Screen)>,
+) {
+    show_screen(
+        &mut commands,
+        &asset_server,
+        &screens,
+        GameState::Paused,
+        bindings.is_changed() || rebinding.is_changed(),
+        || pause_screen_lines(&bindings, &rebinding),
+    );
+}
+
+/// Keeps the screen for `state` up, and redraws it from `lines` whenever
+/// `changed` says what's on it is out of date.
+pub(crate) fn show_screen(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    screens: &Query<(Entity, &Screen)>,
+    state: GameState,
+    changed: bool,
+    lines: impl FnOnce() -> Vec<(String, f32)>,
+) {
+    let mut shown = false;
+    for (entity, screen) in screens.iter() {
+        if screen.0 == state {
+            if changed {
+                commands.entity(entity).despawn_recursive();
+            } else {
+                shown = true;
+            }
+        }
+    }
+    if !shown {
+        spawn_screen(commands, asset_server, state, &lines());
+    }
+}
+
+/// Advances a paused game by a single simulation tick, to step through fights
+/// frame by frame. Only available in debug builds.
+#[cfg(debug_assertions)]
+fn step_paused_simulation(keyboard_input: Res<Input<KeyCode>>, mut clock: ResMut<SimulationClock>) {
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        clock.request_step();
+    }
+}
+
+fn setup_game_over_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    clock: Res<SimulationClock>,
+    stats: Res<RunStats>,
+    rng: Res<GameRng>,
+) {
+    let survived = clock.elapsed().as_secs();
+    let mut lines = vec![
+        ("Game Over".to_string(), 60.0),
+        (
+            format!("Survived {:02}:{:02}", survived / 60, survived % 60),
+            24.0,
+        ),
+        (format!("Kills {}", stats.kills), 24.0),
+        (format!("Experience {}", stats.experience), 24.0),
+    ];
+    for tally in &stats.weapons {
+        lines.push((
+            format!(
+                "{}: {} damage, {} hits, {} kills, {:.1} DPS",
+                tally.weapon,
+                tally.damage,
+                tally.hits,
+                tally.kills,
+                tally.dps(clock.elapsed())
+            ),
+            20.0,
+        ));
+    }
+    lines.push((format!("Seed {}", rng.seed()), 24.0));
+    lines.push(("Press Enter to play again".to_string(), 24.0));
+    spawn_screen(&mut commands, &asset_server, GameState::GameOver, &lines);
+}
+
+fn start_game(actions: Res<ActionState>, mut state: ResMut<State<GameState>>) {
+    if actions.just_pressed(Action::Confirm) {
+        let _ = state.set(GameState::Playing);
+    }
+}
+
+fn toggle_pause(
+    actions: Res<ActionState>,
+    rebinding: Res<Rebinding>,
+    mut state: ResMut<State<GameState>>,
+    mut clock: ResMut<SimulationClock>,
+) {
+    // The key that picked or finished a rebinding isn't also a pause.
+    if rebinding.action.is_some() || rebinding.is_changed() {
+        return;
+    }
+    if actions.just_pressed(Action::Pause) {
+        match state.current() {
+            GameState::Playing => leave_playing(&mut clock, &mut state, GameState::Paused),
+            GameState::Paused => {
+                let _ = state.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Menus, overlays and the keys that move between states.
+pub(crate) fn add_state_screens(app: &mut App) {
+    app.add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(setup_main_menu))
+        .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(start_game))
+        .add_system_set(SystemSet::on_update(GameState::Paused).with_system(show_pause_screen))
+        .add_system_set(
+            SystemSet::on_enter(GameState::GameOver).with_system(setup_game_over_screen),
+        )
+        .add_system_set(SystemSet::on_update(GameState::GameOver).with_system(start_game))
+        .add_system(toggle_pause)
+        .add_system_to_stage(CoreStage::PostUpdate, despawn_screens);
+
+    #[cfg(debug_assertions)]
+    app.add_system_set(SystemSet::on_update(GameState::Paused).with_system(step_paused_simulation));
+}
@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::{DamageRequest, DamageType, SimulationClock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum StatusKind {
+    /// Damage every `interval`.
+    Burn,
+    /// Takes `strength` of the target's speed away, as a fraction.
+    Slow,
+    /// Stops the target from moving, dashing or firing.
+    Freeze,
+    /// Damage every `interval`, but never the last point of health.
+    Poison,
+}
+
+/// What happens when an effect hits a target that already has it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Stacking {
+    /// Starts the duration over.
+    #[default]
+    Refresh,
+    /// Adds a stack, up to `max_stacks`, and starts the duration over. Every
+    /// stack adds the effect's full strength.
+    Intensify { max_stacks: u32 },
+    /// Leaves the running effect alone.
+    Ignore,
+}
+
+/// A status effect that a hit applies, as declared by a weapon or an enemy
+/// archetype.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// Seconds the effect lasts.
+    pub duration: f32,
+    /// Seconds between damage for burn and poison.
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+    /// Damage per interval for burn and poison, or the fraction of speed
+    /// taken away for slow. Unused by freeze.
+    #[serde(default)]
+    pub strength: f32,
+    #[serde(default)]
+    pub stacking: Stacking,
+    /// Seconds after the effect wears off during which it can't be applied
+    /// again.
+    #[serde(default)]
+    pub immunity: f32,
+}
+
+impl StatusEffect {
+    /// Checks that the effect can run its course.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.duration.is_nan() || self.duration < 0.0 {
+            return Err(format!("duration can't be negative, not {}", self.duration));
+        }
+        if self.interval.is_nan() || self.interval <= 0.0 {
+            return Err(format!("interval must be positive, not {}", self.interval));
+        }
+        if self.immunity.is_nan() || self.immunity < 0.0 {
+            return Err(format!("immunity can't be negative, not {}", self.immunity));
+        }
+        Ok(())
+    }
+}
+
+/// Checks every effect in a list, naming the one that's wrong.
+pub(crate) fn validate_effects(effects: &[StatusEffect]) -> Result<(), String> {
+    for (i, effect) in effects.iter().enumerate() {
+        effect
+            .validate()
+            .map_err(|err| format!("effect {} ({:?}): {}", i, effect.kind, err))?;
+    }
+    Ok(())
+}
+
+fn default_interval() -> f32 {
+    1.0
+}
+
+/// The status effects a hit from this entity applies.
+#[derive(Component, Debug, Clone)]
+pub struct AppliesEffects(pub Vec<StatusEffect>);
+
+#[derive(Debug)]
+struct ActiveEffect {
+    effect: StatusEffect,
+    stacks: u32,
+    remaining: Duration,
+    /// Time since the last damage tick.
+    since_tick: Duration,
+    /// The weapon that last applied the effect, which its damage is put down to.
+    weapon: Option<String>,
+}
+
+/// The status effects currently on an entity, and the ones it's immune to for
+/// now. Entities without this component can't be affected.
+#[derive(Component, Debug, Default)]
+pub struct StatusEffects {
+    active: Vec<ActiveEffect>,
+    immune: Vec<(StatusKind, Duration)>,
+}
+
+impl StatusKind {
+    /// The type of damage burn and poison deal.
+    pub fn damage_type(&self) -> DamageType {
+        match self {
+            StatusKind::Burn => DamageType::Fire,
+            _ => DamageType::Physical,
+        }
+    }
+}
+
+impl StatusEffects {
+    /// Applies `effect`, crediting any damage it deals from now on to `weapon`.
+    pub fn apply(&mut self, effect: &StatusEffect, weapon: Option<&str>) {
+        if self.immune.iter().any(|(kind, _)| *kind == effect.kind) {
+            return;
+        }
+        let duration = Duration::from_secs_f32(effect.duration);
+        match self
+            .active
+            .iter_mut()
+            .find(|active| active.effect.kind == effect.kind)
+        {
+            Some(active) => match effect.stacking {
+                Stacking::Refresh => {
+                    active.effect = effect.clone();
+                    active.remaining = duration;
+                    active.weapon = weapon.map(str::to_string);
+                }
+                Stacking::Intensify { max_stacks } => {
+                    active.effect = effect.clone();
+                    active.stacks = (active.stacks + 1).min(max_stacks.max(1));
+                    active.remaining = duration;
+                    active.weapon = weapon.map(str::to_string);
+                }
+                Stacking::Ignore => {}
+            },
+            None => self.active.push(ActiveEffect {
+                effect: effect.clone(),
+                stacks: 1,
+                remaining: duration,
+                since_tick: Duration::ZERO,
+                weapon: weapon.map(str::to_string),
+            }),
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.active.iter().any(|active| active.effect.kind == kind)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.has(StatusKind::Freeze)
+    }
+
+    /// What the entity's speed is multiplied by, from 0 to 1.
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.is_frozen() {
+            return 0.0;
+        }
+        self.active
+            .iter()
+            .filter(|active| active.effect.kind == StatusKind::Slow)
+            .fold(1.0, |multiplier, active| {
+                multiplier * (1.0 - active.effect.strength * active.stacks as f32).max(0.0)
+            })
+    }
+}
+
+/// Runs down every effect's duration and asks for damage over time.
+pub(crate) fn tick_status_effects(
+    clock: Res<SimulationClock>,
+    mut damage_requests: EventWriter<DamageRequest>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+) {
+    let dt = clock.delta();
+    for (entity, mut effects) in query.iter_mut() {
+        let effects = &mut *effects;
+        effects.immune.retain_mut(|(_, remaining)| {
+            *remaining = remaining.saturating_sub(dt);
+            !remaining.is_zero()
+        });
+        for active in effects.active.iter_mut() {
+            let step = dt.min(active.remaining);
+            active.remaining -= step;
+            if !matches!(active.effect.kind, StatusKind::Burn | StatusKind::Poison) {
+                continue;
+            }
+            let interval = Duration::from_secs_f32(active.effect.interval);
+            active.since_tick += step;
+            while active.since_tick >= interval {
+                active.since_tick -= interval;
+                damage_requests.send(DamageRequest {
+                    source: None,
+                    target: entity,
+                    owner: None,
+                    weapon: active.weapon.clone(),
+                    amount: active.effect.strength * active.stacks as f32,
+                    kind: active.effect.kind.damage_type(),
+                    crit: false,
+                    lethal: active.effect.kind != StatusKind::Poison,
+                });
+            }
+        }
+        let immune = &mut effects.immune;
+        effects.active.retain(|active| {
+            if !active.remaining.is_zero() {
+                return true;
+            }
+            if active.effect.immunity > 0.0 {
+                immune.push((
+                    active.effect.kind,
+                    Duration::from_secs_f32(active.effect.immunity),
+                ));
+            }
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn burn() -> StatusEffect {
+        StatusEffect {
+            kind: StatusKind::Burn,
+            duration: 3.0,
+            interval: 0.5,
+            strength: 1.0,
+            stacking: Stacking::Refresh,
+            immunity: 0.0,
+        }
+    }
+
+    #[test]
+    fn bad_effects_are_rejected() {
+        assert_eq!(burn().validate(), Ok(()));
+        let instant = StatusEffect {
+            duration: 0.0,
+            ..burn()
+        };
+        assert_eq!(instant.validate(), Ok(()));
+        for bad in [
+            StatusEffect {
+                duration: -1.0,
+                ..burn()
+            },
+            StatusEffect {
+                interval: 0.0,
+                ..burn()
+            },
+            StatusEffect {
+                interval: -0.5,
+                ..burn()
+            },
+            StatusEffect {
+                immunity: -2.0,
+                ..burn()
+            },
+            StatusEffect {
+                duration: f32::NAN,
+                ..burn()
+            },
+        ] {
+            assert!(bad.validate().is_err(), "{:?} was accepted", bad);
+        }
+        let error = validate_effects(&[
+            burn(),
+            StatusEffect {
+                immunity: -2.0,
+                ..burn()
+            },
+        ]);
+        assert!(error.unwrap_err().starts_with("effect 1 (Burn)"));
+    }
+}
@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::{
+    state::{self, Screen},
+    Action, ActionState, Attraction, CritBonus, Experience, GameRng, GameState, Health,
+    InputBindings, Player, SimulationClock, Velocity, Weapon, WeaponDefinitions, Weapons,
+};
+
+/// How many upgrades are offered on each level-up.
+const UPGRADE_CHOICES: usize = 3;
+
+/// The player's level, which goes up as experience is collected. See
+/// `experience_for_level`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+    pub level: u32,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level { level: 1 }
+    }
+}
+
+/// The total experience needed to reach `level`. Each level costs 5 more than
+/// the last: 5 for level 2, 15 for level 3, 30 for level 4 and so on.
+pub fn experience_for_level(level: u32) -> u32 {
+    5 * level.saturating_sub(1) * level / 2
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassiveStat {
+    MoveSpeed,
+    MaxHealth,
+    /// Pulls experience drops in from further away.
+    Magnet,
+    CritChance,
+    /// Makes crits hit harder.
+    CritMultiplier,
+}
+
+/// Something the player can pick on leveling up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Upgrade {
+    NewWeapon(String),
+    /// Takes a weapon the player has to `level`.
+    WeaponLevel {
+        weapon: String,
+        level: u32,
+    },
+    Passive(PassiveStat),
+}
+
+impl Upgrade {
+    pub fn description(&self) -> String {
+        match self {
+            Upgrade::NewWeapon(weapon) => format!("New weapon: {}", weapon),
+            Upgrade::WeaponLevel { weapon, level } => format!("{} level {}", weapon, level),
+            Upgrade::Passive(PassiveStat::MoveSpeed) => "Move speed +10%".to_string(),
+            Upgrade::Passive(PassiveStat::MaxHealth) => "Max health +20".to_string(),
+            Upgrade::Passive(PassiveStat::Magnet) => "Pickup range +25".to_string(),
+            Upgrade::Passive(PassiveStat::CritChance) => "Crit chance +5%".to_string(),
+            Upgrade::Passive(PassiveStat::CritMultiplier) => "Crit multiplier +0.25".to_string(),
+        }
+    }
+}
+
+/// The upgrades on offer while in `GameState::LevelUp`.
+pub struct UpgradeChoice {
+    pub level: u32,
+    pub options: Vec<Upgrade>,
+    /// The option that confirming picks, moved with up and down.
+    pub selected: usize,
+}
+
+/// Picks the first upgrade on offer straight away instead of stopping to ask,
+/// for when there is nobody to ask. Since the options are drawn from
+/// `GameRng`, a run stays reproducible from its seed.
+pub struct AutoPickUpgrades;
+
+fn roll_upgrades(
+    rng: &mut GameRng,
+    definitions: &WeaponDefinitions,
+    weapons: &Weapons,
+) -> Vec<Upgrade> {
+    let mut pool = Vec::new();
+    for id in definitions.ids() {
+        match weapons.get(id) {
+            None => pool.push(Upgrade::NewWeapon(id.to_string())),
+            Some(weapon) if weapon.level() < weapon.max_level() => {
+                pool.push(Upgrade::WeaponLevel {
+                    weapon: id.to_string(),
+                    level: weapon.level() + 1,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    pool.extend(
+        [
+            PassiveStat::MoveSpeed,
+            PassiveStat::MaxHealth,
+            PassiveStat::Magnet,
+            PassiveStat::CritChance,
+            PassiveStat::CritMultiplier,
+        ]
+        .map(Upgrade::Passive),
+    );
+    pool.shuffle(rng);
+    pool.truncate(UPGRADE_CHOICES);
+    pool
+}
+
+fn apply_upgrade(
+    commands: &mut Commands,
+    definitions: &WeaponDefinitions,
+    upgrade: &Upgrade,
+    (entity, mut weapons, mut velocity, mut health, mut crit_bonus, attraction): PlayerStats,
+) {
+    match upgrade {
+        Upgrade::NewWeapon(id) => match definitions.get(id) {
+            Some(definition) => weapons.add(Weapon::new(definition.clone())),
+            None => warn!("No weapon definition for {:?}", id),
+        },
+        Upgrade::WeaponLevel { weapon, .. } => {
+            if let Some(weapon) = weapons.get_mut(weapon) {
+                weapon.level_up();
+            }
+        }
+        Upgrade::Passive(PassiveStat::MoveSpeed) => velocity.speed *= 1.1,
+        Upgrade::Passive(PassiveStat::MaxHealth) => {
+            health.max += 20;
+            health.current += 20;
+        }
+        Upgrade::Passive(PassiveStat::CritChance) => crit_bonus.chance += 0.05,
+        Upgrade::Passive(PassiveStat::CritMultiplier) => crit_bonus.multiplier += 0.25,
+        Upgrade::Passive(PassiveStat::Magnet) => match attraction {
+            Some(mut attraction) => attraction.radius += 25.0,
+            None => {
+                commands.entity(entity).insert(Attraction {
+                    radius: 50.0,
+                    force: 100.0,
+                });
+            }
+        },
+    }
+}
+
+type PlayerStats<'a> = (
+    Entity,
+    Mut<'a, Weapons>,
+    Mut<'a, Velocity>,
+    Mut<'a, Health>,
+    Mut<'a, CritBonus>,
+    Option<Mut<'a, Attraction>>,
+);
+
+/// Levels the player up once their experience is enough, then either stops
+/// the game to offer a choice of upgrades or, with `AutoPickUpgrades`, takes
+/// one right away. At most one level is gained per tick.
+pub(crate) fn check_level_up(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    mut clock: ResMut<SimulationClock>,
+    mut state: ResMut<State<GameState>>,
+    auto_pick: Option<Res<AutoPickUpgrades>>,
+    definitions: Res<WeaponDefinitions>,
+    mut players: Query<
+        (
+            Entity,
+            &Experience,
+            &mut Level,
+            &mut Weapons,
+            &mut Velocity,
+            &mut Health,
+            &mut CritBonus,
+            Option<&mut Attraction>,
+        ),
+        With<Player>,
+    >,
+) {
+    for (entity, experience, mut level, weapons, velocity, health, crit_bonus, attraction) in
+        players.iter_mut()
+    {
+        if experience.amount < experience_for_level(level.level + 1) {
+            continue;
+        }
+        level.level += 1;
+        let options = roll_upgrades(&mut rng, &definitions, &weapons);
+        if options.is_empty() {
+            continue;
+        }
+        if auto_pick.is_some() {
+            let player = (entity, weapons, velocity, health, crit_bonus, attraction);
+            apply_upgrade(&mut commands, &definitions, &options[0], player);
+        } else {
+            commands.insert_resource(UpgradeChoice {
+                level: level.level,
+                options,
+                selected: 0,
+            });
+            state::leave_playing(&mut clock, &mut state, GameState::LevelUp);
+        }
+    }
+}
+
+fn level_up_screen_lines(choice: &UpgradeChoice, bindings: &InputBindings) -> Vec<(String, f32)> {
+    let mut lines = vec![
+        ("Level up!".to_string(), 60.0),
+        (format!("Level {}", choice.level), 24.0),
+    ];
+    for (i, (upgrade, action)) in choice.options.iter().zip(CHOICE_ACTIONS).enumerate() {
+        let marker = if i == choice.selected { ">" } else { " " };
+        lines.push((
+            format!(
+                "{} {} [{}]",
+                marker,
+                upgrade.description(),
+                bindings.describe_keys(action)
+            ),
+            24.0,
+        ));
+    }
+    lines.push((
+        format!(
+            "{} and {} to move, {} to pick",
+            bindings.describe_inputs(Action::MoveUp),
+            bindings.describe_inputs(Action::MoveDown),
+            bindings.describe_inputs(Action::Confirm)
+        ),
+        20.0,
+    ));
+    lines
+}
+
+/// Shows the level-up screen, and redraws it whenever the selection moves.
+fn show_level_up_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    choice: Res<UpgradeChoice>,
+    bindings: Res<InputBindings>,
+    screens: Query<(Entity, &Screen)>,
+) {
+    state::show_screen(
+        &mut commands,
+        &asset_server,
+        &screens,
+        GameState::LevelUp,
+        choice.is_changed(),
+        || level_up_screen_lines(&choice, &bindings),
+    );
+}
+
+/// The actions that pick each option directly, in the order they're listed.
+const CHOICE_ACTIONS: [Action; 4] = [
+    Action::Choose1,
+    Action::Choose2,
+    Action::Choose3,
+    Action::Choose4,
+];
+
+fn choose_upgrade(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut state: ResMut<State<GameState>>,
+    mut choice: ResMut<UpgradeChoice>,
+    definitions: Res<WeaponDefinitions>,
+    mut players: Query<
+        (
+            Entity,
+            &mut Weapons,
+            &mut Velocity,
+            &mut Health,
+            &mut CritBonus,
+            Option<&mut Attraction>,
+        ),
+        With<Player>,
+    >,
+) {
+    let count = choice.options.len();
+    if actions.just_pressed(Action::MoveUp) {
+        choice.selected = (choice.selected + count - 1) % count;
+    }
+    if actions.just_pressed(Action::MoveDown) {
+        choice.selected = (choice.selected + 1) % count;
+    }
+    let picked = CHOICE_ACTIONS
+        .iter()
+        .take(count)
+        .position(|&action| actions.just_pressed(action))
+        .or_else(|| {
+            actions
+                .just_pressed(Action::Confirm)
+                .then(|| choice.selected)
+        })
+        .map(|i| choice.options[i].clone());
+    if let Some(upgrade) = picked {
+        for player in players.iter_mut() {
+            apply_upgrade(&mut commands, &definitions, &upgrade, player);
+        }
+        commands.remove_resource::<UpgradeChoice>();
+        let _ = state.pop();
+    }
+}
+
+/// The screen for picking an upgrade after leveling up.
+pub(crate) fn add_level_up_screen(app: &mut App) {
+    app.add_system_set(
+        SystemSet::on_update(GameState::LevelUp)
+            .with_system(choose_upgrade)
+            .with_system(show_level_up_screen.after(choose_upgrade)),
+    );
+}
@@ -0,0 +1,470 @@
+use bevy::{prelude::*, utils::HashMap};
+use rand::Rng;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::{
+    arena::BounceOffArenaEdges, data, state::RunEntity, status, AppliesEffects, Bullet, Collider,
+    CollisionLayers, CritBonus, Damage, DamageType, Enemy, GameRng, Knockback, Lifetime, Owner,
+    Punchthrough, SimulationClock, StatusEffect, StatusEffects, Sweep, Velocity,
+};
+
+/// How a weapon picks the direction of each projectile it fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Targeting {
+    /// Aims at the closest enemies, one per projectile. Doesn't fire when
+    /// there are no enemies.
+    NearestEnemy,
+    /// Fires in a random direction.
+    RandomDirection,
+}
+
+/// What a weapon's projectiles look like and do, apart from their stats.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectileTemplate {
+    pub color: (f32, f32, f32),
+    /// Bounces off the edges of the `ArenaBounds` rather than flying off.
+    #[serde(default)]
+    pub bounce: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeaponStats {
+    /// Seconds between shots.
+    pub cooldown: f32,
+    pub damage: u32,
+    pub size: f32,
+    pub speed: f32,
+    /// Seconds before a projectile disappears.
+    pub lifetime: f32,
+    /// How many enemies a projectile can hit. `None` for no limit.
+    pub pierce: Option<u32>,
+    /// Projectiles fired per shot.
+    pub count: u32,
+    /// How hard a projectile pushes back what it hits. See `Knockback`.
+    #[serde(default)]
+    pub knockback: f32,
+    /// The chance of a hit being a crit, from 0 to 1.
+    #[serde(default)]
+    pub crit_chance: f32,
+    /// What a crit multiplies damage by.
+    #[serde(default = "default_crit_multiplier")]
+    pub crit_multiplier: f32,
+    /// How far damage can land either side of `damage`, as a fraction of it.
+    #[serde(default)]
+    pub variance: f32,
+}
+
+fn default_crit_multiplier() -> f32 {
+    2.0
+}
+
+impl WeaponStats {
+    fn apply(&mut self, bonus: &WeaponLevel) {
+        self.cooldown = (self.cooldown + bonus.cooldown).max(MIN_COOLDOWN);
+        self.damage += bonus.damage;
+        self.size += bonus.size;
+        self.speed += bonus.speed;
+        self.lifetime += bonus.lifetime;
+        self.pierce = self.pierce.map(|pierce| pierce + bonus.pierce);
+        self.count += bonus.count;
+        self.knockback += bonus.knockback;
+        self.crit_chance += bonus.crit_chance;
+        self.crit_multiplier += bonus.crit_multiplier;
+        self.variance += bonus.variance;
+    }
+
+    /// Checks that a weapon with these stats can fire.
+    fn validate(&self) -> Result<(), String> {
+        if self.cooldown.is_nan() || self.cooldown <= 0.0 {
+            return Err(format!("cooldown must be positive, not {}", self.cooldown));
+        }
+        if self.lifetime.is_nan() || self.lifetime < 0.0 {
+            return Err(format!("lifetime can't be negative, not {}", self.lifetime));
+        }
+        if self.speed.is_nan() || self.speed < 0.0 {
+            return Err(format!("speed can't be negative, not {}", self.speed));
+        }
+        if !(0.0..=1.0).contains(&self.crit_chance) {
+            return Err(format!(
+                "crit chance must be from 0 to 1, not {}",
+                self.crit_chance
+            ));
+        }
+        if self.variance.is_nan() || self.variance < 0.0 {
+            return Err(format!("variance can't be negative, not {}", self.variance));
+        }
+        Ok(())
+    }
+}
+
+const MIN_COOLDOWN: f32 = 0.05;
+
+/// What one level-up adds to a weapon's stats. Fields left out add nothing.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WeaponLevel {
+    pub cooldown: f32,
+    pub damage: u32,
+    pub size: f32,
+    pub speed: f32,
+    pub lifetime: f32,
+    pub pierce: u32,
+    pub count: u32,
+    pub knockback: f32,
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+    pub variance: f32,
+}
+
+/// One kind of weapon, as described by a file in `assets/weapons`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeaponDefinition {
+    pub id: String,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    pub targeting: Targeting,
+    pub projectile: ProjectileTemplate,
+    /// The stats at level 1.
+    pub stats: WeaponStats,
+    /// The bonus for reaching each level after the first, in order.
+    #[serde(default)]
+    pub levels: Vec<WeaponLevel>,
+    /// Status effects that the projectiles apply to whatever they hit.
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>,
+}
+
+impl WeaponDefinition {
+    /// Checks that the weapon can fire at every level.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut stats = self.stats.clone();
+        stats.validate()?;
+        for (i, bonus) in self.levels.iter().enumerate() {
+            stats.apply(bonus);
+            stats
+                .validate()
+                .map_err(|err| format!("level {}: {}", i + 2, err))?;
+        }
+        status::validate_effects(&self.effects)
+    }
+}
+
+/// Every weapon definition, by ID. Loaded like `EnemyArchetypes`.
+#[derive(Default)]
+pub struct WeaponDefinitions {
+    definitions: HashMap<String, WeaponDefinition>,
+}
+
+const BUILT_IN_WEAPONS: &[&str] = &[
+    include_str!("../assets/weapons/bullet.ron"),
+    include_str!("../assets/weapons/bouncer.ron"),
+    include_str!("../assets/weapons/firebolt.ron"),
+];
+
+impl WeaponDefinitions {
+    pub fn load() -> Self {
+        let mut definitions = WeaponDefinitions::default();
+        data::load_ron_definitions(
+            BUILT_IN_WEAPONS,
+            "weapons",
+            "weapon",
+            WeaponDefinition::validate,
+            |definition| definitions.insert(definition),
+        );
+        definitions
+    }
+
+    pub fn insert(&mut self, definition: WeaponDefinition) {
+        self.definitions.insert(definition.id.clone(), definition);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&WeaponDefinition> {
+        self.definitions.get(id)
+    }
+
+    /// Every weapon ID, sorted so that anything picking from them is
+    /// reproducible.
+    pub fn ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.definitions.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// The id of the weapon a projectile was fired from, so that its damage and
+/// kills can be put down to that weapon.
+#[derive(Component, Debug, Clone)]
+pub struct FiredFrom(pub String);
+
+/// A weapon held by an entity, firing on its own cooldown.
+#[derive(Debug)]
+pub struct Weapon {
+    pub definition: WeaponDefinition,
+    level: u32,
+    stats: WeaponStats,
+    cooldown: Timer,
+}
+
+impl Weapon {
+    pub fn new(definition: WeaponDefinition) -> Self {
+        let stats = definition.stats.clone();
+        let cooldown = Timer::new(Duration::from_secs_f32(stats.cooldown), true);
+        Weapon {
+            definition,
+            level: 1,
+            stats,
+            cooldown,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.definition.id
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn max_level(&self) -> u32 {
+        self.definition.levels.len() as u32 + 1
+    }
+
+    /// The stats with every level-up so far applied.
+    pub fn stats(&self) -> &WeaponStats {
+        &self.stats
+    }
+
+    /// Moves up to the next level, unless already at the last one.
+    pub fn level_up(&mut self) {
+        if let Some(bonus) = self.definition.levels.get(self.level as usize - 1) {
+            self.stats.apply(bonus);
+            self.cooldown
+                .set_duration(Duration::from_secs_f32(self.stats.cooldown));
+            self.level += 1;
+        }
+    }
+}
+
+/// Every weapon an entity fires, in the order they were picked up.
+#[derive(Component, Debug, Default)]
+pub struct Weapons {
+    weapons: Vec<Weapon>,
+}
+
+impl Weapons {
+    pub fn add(&mut self, weapon: Weapon) {
+        self.weapons.push(weapon);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Weapon> {
+        self.weapons.iter().find(|weapon| weapon.id() == id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Weapon> {
+        self.weapons.iter_mut().find(|weapon| weapon.id() == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Weapon> {
+        self.weapons.iter()
+    }
+}
+
+#[derive(Bundle)]
+struct ProjectileBundle {
+    bullet: Bullet,
+    speed: Velocity,
+    damage: Damage,
+    owner: Owner,
+    lifetime: Lifetime,
+    punchthrough: Punchthrough,
+    run_entity: RunEntity,
+    layers: CollisionLayers,
+    collider: Collider,
+    sweep: Sweep,
+    #[bundle]
+    sprite: SpriteBundle,
+}
+
+fn spawn_projectile(
+    commands: &mut Commands,
+    owner: Entity,
+    weapon: &Weapon,
+    crit_bonus: CritBonus,
+    translation: Vec3,
+    direction: Vec3,
+) {
+    let stats = &weapon.stats;
+    let color = weapon.definition.projectile.color;
+    let mut projectile = commands.spawn_bundle(ProjectileBundle {
+        damage: Damage {
+            damage: stats.damage,
+            kind: weapon.definition.damage_type,
+            variance: stats.variance,
+            crit_chance: stats.crit_chance + crit_bonus.chance,
+            crit_multiplier: stats.crit_multiplier + crit_bonus.multiplier,
+        },
+        speed: Velocity {
+            speed: stats.speed,
+            direction,
+        },
+        sprite: SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgb(color.0, color.1, color.2),
+                ..default()
+            },
+            transform: Transform {
+                scale: Vec3::new(stats.size, stats.size, 1.0),
+                translation,
+                ..default()
+            },
+            ..default()
+        },
+        lifetime: Lifetime {
+            timer: Timer::new(Duration::from_secs_f32(stats.lifetime), false),
+        },
+        punchthrough: Punchthrough {
+            amount: stats.pierce.unwrap_or(u32::MAX),
+        },
+        layers: CollisionLayers::new(CollisionLayers::PLAYER_PROJECTILE, CollisionLayers::ENEMY),
+        collider: Collider::circle(stats.size / 2.0),
+        sweep: Sweep::default(),
+        run_entity: RunEntity,
+        bullet: Bullet,
+        owner: Owner(Some(owner)),
+    });
+    projectile.insert(FiredFrom(weapon.id().to_string()));
+    if weapon.definition.projectile.bounce {
+        projectile.insert(BounceOffArenaEdges);
+    }
+    if stats.knockback > 0.0 {
+        projectile.insert(Knockback {
+            force: stats.knockback,
+        });
+    }
+    if !weapon.definition.effects.is_empty() {
+        projectile.insert(AppliesEffects(weapon.definition.effects.clone()));
+    }
+}
+
+pub(crate) fn fire_weapons(
+    mut commands: Commands,
+    clock: Res<SimulationClock>,
+    mut rng: ResMut<GameRng>,
+    mut shooters: Query<(
+        Entity,
+        &mut Weapons,
+        &Transform,
+        Option<&StatusEffects>,
+        Option<&CritBonus>,
+    )>,
+    targets: Query<&Transform, With<Enemy>>,
+) {
+    let dt = clock.delta();
+    for (owner, mut weapons, transform, effects, crit_bonus) in shooters.iter_mut() {
+        let crit_bonus = crit_bonus.copied().unwrap_or_default();
+        if effects.is_some_and(StatusEffects::is_frozen) {
+            continue;
+        }
+        let origin = transform.translation;
+        for weapon in weapons.weapons.iter_mut() {
+            weapon.cooldown.tick(dt);
+            if !weapon.cooldown.finished() {
+                continue;
+            }
+            let count = weapon.stats.count as usize;
+            let directions: Vec<Vec3> = match weapon.definition.targeting {
+                Targeting::NearestEnemy => {
+                    let mut nearest: Vec<(f32, Vec3)> = targets
+                        .iter()
+                        .map(|target| {
+                            (
+                                target.translation.distance_squared(origin),
+                                target.translation,
+                            )
+                        })
+                        .collect();
+                    nearest.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Tried to compare a NaN"));
+                    // With fewer enemies than projectiles, go around again.
+                    nearest
+                        .iter()
+                        .cycle()
+                        .take(count)
+                        .map(|(_, target)| (*target - origin).normalize_or_zero())
+                        .collect()
+                }
+                Targeting::RandomDirection => (0..count)
+                    .map(|_| {
+                        Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0)
+                            .normalize_or_zero()
+                    })
+                    .collect(),
+            };
+            for direction in directions {
+                spawn_projectile(&mut commands, owner, weapon, crit_bonus, origin, direction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built_in() -> Vec<WeaponDefinition> {
+        BUILT_IN_WEAPONS
+            .iter()
+            .map(|source| ron::from_str(source).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn built_in_weapons_are_valid() {
+        for definition in built_in() {
+            assert_eq!(definition.validate(), Ok(()), "{}", definition.id);
+        }
+    }
+
+    #[test]
+    fn bad_weapons_are_rejected() {
+        let weapon = built_in().remove(0);
+        let with_stats = |change: fn(&mut WeaponStats)| {
+            let mut weapon = weapon.clone();
+            change(&mut weapon.stats);
+            weapon
+        };
+        let with_level = |level: WeaponLevel| {
+            let mut weapon = weapon.clone();
+            weapon.levels.push(level);
+            weapon
+        };
+        for bad in [
+            with_stats(|stats| stats.cooldown = 0.0),
+            with_stats(|stats| stats.cooldown = -1.0),
+            with_stats(|stats| stats.lifetime = -1.0),
+            with_stats(|stats| stats.speed = -1.0),
+            with_stats(|stats| stats.crit_chance = -0.1),
+            with_stats(|stats| stats.crit_chance = 1.5),
+            with_stats(|stats| stats.variance = -0.5),
+            // Levels are checked with everything before them applied.
+            with_level(WeaponLevel {
+                lifetime: -100.0,
+                ..default()
+            }),
+            with_level(WeaponLevel {
+                crit_chance: 1.0,
+                ..default()
+            }),
+        ] {
+            assert!(bad.validate().is_err(), "{:?} was accepted", bad.stats);
+        }
+        assert_eq!(
+            with_level(WeaponLevel {
+                cooldown: -100.0,
+                ..default()
+            })
+            .validate(),
+            Ok(())
+        );
+    }
+}